@@ -0,0 +1,11 @@
+-- Add migration script here
+create table if not EXISTS play_session (
+  id BIGSERIAL PRIMARY KEY,
+  player_name VARCHAR(25) NOT NULL,
+  started_at TIMESTAMPTZ NOT NULL,
+  ended_at TIMESTAMPTZ,
+  end_reason TEXT
+);
+
+create index if not EXISTS play_session_player_started_at
+  on play_session (LOWER(player_name), started_at);
@@ -0,0 +1,14 @@
+-- Add migration script here
+create table if not EXISTS container_stats (
+  id BIGSERIAL PRIMARY KEY,
+  recorded_at TIMESTAMPTZ NOT NULL,
+  cpu_percent DOUBLE PRECISION NOT NULL,
+  memory_usage BIGINT NOT NULL,
+  memory_limit BIGINT NOT NULL,
+  network_rx BIGINT NOT NULL,
+  network_tx BIGINT NOT NULL,
+  block_read BIGINT NOT NULL,
+  block_write BIGINT NOT NULL
+);
+
+create index if not EXISTS container_stats_recorded_at on container_stats (recorded_at);
@@ -0,0 +1,14 @@
+-- Add migration script here
+create table if not EXISTS event_subscription (
+  guild_id BIGINT NOT NULL,
+  channel_id BIGINT NOT NULL,
+  event_type VARCHAR(20) NOT NULL,
+  PRIMARY KEY (guild_id, channel_id, event_type)
+);
+
+-- Channels that announced joins keep doing so
+insert into event_subscription (guild_id, channel_id, event_type)
+  select guild_id, channel_id, 'join' from player_joined_server_channel
+  on conflict do nothing;
+
+drop table if EXISTS player_joined_server_channel;
@@ -0,0 +1,7 @@
+-- Add migration script here
+create table if not EXISTS role_permission (
+  guild_id BIGINT NOT NULL,
+  role_id BIGINT NOT NULL,
+  capability VARCHAR(20) NOT NULL,
+  PRIMARY KEY (guild_id, role_id, capability)
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+create table if not EXISTS audit_log (
+  id BIGSERIAL PRIMARY KEY,
+  created_at TIMESTAMPTZ NOT NULL,
+  guild_id BIGINT,
+  user_id BIGINT NOT NULL,
+  user_name TEXT NOT NULL,
+  command VARCHAR(32) NOT NULL,
+  options TEXT NOT NULL,
+  result TEXT NOT NULL
+);
+
+create index if not EXISTS audit_log_guild_created_at on audit_log (guild_id, created_at);
@@ -0,0 +1,16 @@
+-- Add migration script here
+create table if not EXISTS schedule (
+  id BIGSERIAL PRIMARY KEY,
+  guild_id BIGINT NOT NULL,
+  created_by BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  -- 0 is monday, NULL runs every day
+  weekday SMALLINT,
+  time TIME NOT NULL,
+  timezone TEXT NOT NULL,
+  action VARCHAR(20) NOT NULL,
+  argument TEXT,
+  channel_id BIGINT,
+  -- The last occurrence that was run or skipped
+  last_run_at TIMESTAMPTZ NOT NULL
+);
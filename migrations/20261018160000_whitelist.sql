@@ -0,0 +1,11 @@
+-- Add migration script here
+create table if not EXISTS whitelist (
+  guild_id BIGINT NOT NULL,
+  player_name VARCHAR(16) NOT NULL,
+  -- Discord user who asked for the player to be whitelisted
+  user_id BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL
+);
+
+-- Minecraft names are not case sensitive
+create unique index if not EXISTS whitelist_guild_player_name ON whitelist (guild_id, lower(player_name));
@@ -0,0 +1,8 @@
+-- Add migration script here
+create table if not EXISTS account_link (
+  discord_id BIGINT PRIMARY KEY,
+  player_name VARCHAR(16) NOT NULL,
+  linked_at TIMESTAMPTZ NOT NULL
+);
+
+create unique index if not EXISTS account_link_player_name ON account_link (lower(player_name));
@@ -0,0 +1,10 @@
+-- Add migration script here
+create table if not EXISTS backup (
+  id BIGSERIAL PRIMARY KEY,
+  created_at TIMESTAMPTZ NOT NULL,
+  -- Directory under BACKUP_DIR holding one tar archive per world directory
+  name TEXT NOT NULL,
+  size_bytes BIGINT NOT NULL,
+  trigger VARCHAR(20) NOT NULL,
+  created_by BIGINT
+);
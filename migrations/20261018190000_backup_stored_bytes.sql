@@ -0,0 +1,6 @@
+-- Add migration script here
+-- How much of the backup was not in the chunk store yet
+ALTER TABLE backup ADD COLUMN IF NOT EXISTS stored_bytes BIGINT;
+-- Backups from before the chunk store are full tar archives
+UPDATE backup SET stored_bytes = size_bytes WHERE stored_bytes IS NULL;
+ALTER TABLE backup ALTER COLUMN stored_bytes SET NOT NULL;
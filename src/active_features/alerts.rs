@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, Http};
+
+use crate::{commands::CommandResult, server_state::ServerState};
+
+/// Tells the admins about something that needs their attention, in the alert channel if there is one.
+pub async fn alert(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    content: impl Into<String>,
+) -> CommandResult {
+    let content = content.into();
+    log::warn!("Alert: {content}");
+
+    let Some(channel_id) = server_state.bot_config.alert_channel_id else {
+        return Ok(());
+    };
+    ChannelId::new(channel_id)
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+    Ok(())
+}
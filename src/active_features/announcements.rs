@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, Http};
+use serenity::utils::MessageBuilder;
+
+use crate::{
+    commands::CommandResult,
+    log_parser::{AdvancementKind, ServerEvent},
+    server_state::ServerState,
+    sql::subscriptions::{EventSubscription, EventType},
+};
+
+/// Posts `content` in every channel subscribed to `event_type`.
+pub async fn announce(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    event_type: EventType,
+    content: String,
+) -> CommandResult {
+    for channel_id in EventSubscription::get_channels(&server_state.db, event_type).await? {
+        log::debug!("Sending message in channel {}: {content}", channel_id.get());
+        ChannelId::new(channel_id.get())
+            .send_message(
+                &http,
+                CreateMessage::new()
+                    .content(&content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn announce_death(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    event: &ServerEvent,
+) -> CommandResult {
+    let ServerEvent::Death { message, .. } = event else {
+        return Ok(());
+    };
+
+    let content = MessageBuilder::new()
+        .push(":skull: ")
+        .push_safe(message.as_str())
+        .build();
+    announce(server_state, http, EventType::Death, content).await
+}
+
+pub async fn announce_advancement(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    event: &ServerEvent,
+) -> CommandResult {
+    let ServerEvent::Advancement {
+        player,
+        kind,
+        advancement,
+    } = event
+    else {
+        return Ok(());
+    };
+
+    announce(
+        server_state,
+        http,
+        EventType::Advancement,
+        advancement_message(player, *kind, advancement),
+    )
+    .await
+}
+
+fn advancement_message(player: &str, kind: AdvancementKind, advancement: &str) -> String {
+    let (emoji, made) = match kind {
+        AdvancementKind::Advancement => (":medal:", "has made the advancement"),
+        AdvancementKind::Goal => (":dart:", "has reached the goal"),
+        AdvancementKind::Challenge => (":trophy:", "has completed the challenge"),
+    };
+
+    MessageBuilder::new()
+        .push(emoji)
+        .push(" ")
+        .push_safe(player)
+        .push(format!(" {made} "))
+        .push_bold_safe(format!("[{advancement}]"))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log_parser::AdvancementKind;
+
+    use super::advancement_message;
+
+    #[test]
+    fn advancement_messages() {
+        // Advancement
+        assert_eq!(
+            advancement_message("sally", AdvancementKind::Advancement, "Stone Age"),
+            ":medal: sally has made the advancement **[Stone Age]**"
+        );
+
+        // Goal
+        assert_eq!(
+            advancement_message("sally", AdvancementKind::Goal, "Sky's the Limit"),
+            ":dart: sally has reached the goal **[Sky's the Limit]**"
+        );
+
+        // Challenge
+        assert_eq!(
+            advancement_message("sally", AdvancementKind::Challenge, "How Did We Get Here?"),
+            ":trophy: sally has completed the challenge **[How Did We Get Here?]**"
+        );
+    }
+
+    #[test]
+    fn escapes_player_names() {
+        assert_eq!(
+            advancement_message("__sally__", AdvancementKind::Advancement, "Stone Age"),
+            ":medal: \\_\\_sally\\_\\_ has made the advancement **[Stone Age]**"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, Context, CreateAllowedMentions, CreateMessage, Http, Message};
+use serenity::utils::MessageBuilder;
+
+use crate::{
+    commands::CommandResult,
+    console::{self, ConsoleError},
+    log_parser::ServerEvent,
+    server_state::ServerState,
+};
+
+/// Vanilla clients cannot type more than this into chat, so keep Discord to the same limit
+const MAX_MESSAGE_LENGTH: usize = 256;
+const MAX_AUTHOR_LENGTH: usize = 32;
+
+/// Mirrors in-game chat into the bridge channel.
+pub async fn relay_to_discord(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    event: &ServerEvent,
+) -> CommandResult {
+    let Some(channel_id) = server_state.bot_config.chat_bridge_channel_id else {
+        return Ok(());
+    };
+    let ServerEvent::Chat { player, message } = event else {
+        return Ok(());
+    };
+
+    let content = MessageBuilder::new()
+        .push_bold_safe(player.as_str())
+        .push(": ")
+        .push_safe(message.as_str())
+        .build();
+    ChannelId::new(channel_id)
+        .send_message(
+            http,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Sends messages posted in the bridge channel into the game.
+pub async fn relay_to_minecraft(
+    server_state: &ServerState,
+    ctx: &Context,
+    msg: &Message,
+) -> Result<(), ConsoleError> {
+    if server_state.bot_config.chat_bridge_channel_id != Some(msg.channel_id.get())
+        || msg.author.bot
+    {
+        return Ok(());
+    }
+
+    let author = msg
+        .member
+        .as_ref()
+        .and_then(|member| member.nick.clone())
+        .or_else(|| msg.author.global_name.clone())
+        .unwrap_or_else(|| msg.author.name.clone());
+
+    let mut content = msg.content_safe(&ctx.cache);
+    if !msg.attachments.is_empty() {
+        content.push_str(" [attachment]");
+    }
+    let Some(command) = tellraw_command(&author, &content) else {
+        return Ok(());
+    };
+
+    console::send_command(server_state, &command).await?;
+    Ok(())
+}
+
+/// Builds a `tellraw` command that shows `message` from a Discord user to every player.
+///
+/// Returns `None` if there is nothing left to send after cleaning up the message.
+pub fn tellraw_command(author: &str, message: &str) -> Option<String> {
+    let author = sanitise(author, MAX_AUTHOR_LENGTH);
+    let message = sanitise(message, MAX_MESSAGE_LENGTH);
+    if message.is_empty() {
+        return None;
+    }
+
+    // serde_json takes care of quotes and backslashes; the text is never parsed as a selector
+    let components = serde_json::json!([
+        "",
+        { "text": "[Discord] ", "color": "blue" },
+        { "text": format!("<{author}> ") },
+        { "text": message },
+    ]);
+    Some(format!("tellraw @a {components}"))
+}
+
+/// Flattens the text onto one line, drops control characters and caps the length.
+fn sanitise(s: &str, max_length: usize) -> String {
+    let flattened = s
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        // The section sign starts a formatting code in game
+        .filter(|c| *c != '§')
+        .collect::<String>();
+
+    if flattened.chars().count() <= max_length {
+        return flattened;
+    }
+    let mut truncated: String = flattened.chars().take(max_length - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_MESSAGE_LENGTH, sanitise, tellraw_command};
+
+    #[test]
+    fn builds_tellraw() {
+        assert_eq!(
+            tellraw_command("sally", "hello").unwrap(),
+            r#"tellraw @a ["",{"color":"blue","text":"[Discord] "},{"text":"<sally> "},{"text":"hello"}]"#
+        );
+    }
+
+    #[test]
+    fn escapes_json() {
+        let command = tellraw_command("sa\"lly", r#"he said "hi" \o/"#).unwrap();
+        assert!(command.contains(r#"{"text":"<sa\"lly> "}"#), "{command}");
+        assert!(
+            command.contains(r#"{"text":"he said \"hi\" \\o/"}"#),
+            "{command}"
+        );
+
+        let command = tellraw_command("sally", r#""}]} ,{"text":"@a"#).unwrap();
+        let json = command.strip_prefix("tellraw @a ").unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn single_line() {
+        let command = tellraw_command("sally", "first\nsecond\r\n\tthird").unwrap();
+        assert!(
+            command.contains(r#"{"text":"first second third"}"#),
+            "{command}"
+        );
+        assert!(!command.contains('\n'));
+    }
+
+    #[test]
+    fn strips_formatting_codes() {
+        assert_eq!(sanitise("§khidden§r text", 100), "khiddenr text");
+    }
+
+    #[test]
+    fn empty_messages() {
+        assert_eq!(tellraw_command("sally", ""), None);
+        assert_eq!(tellraw_command("sally", " \n\t "), None);
+    }
+
+    #[test]
+    fn length_limit() {
+        let long = "a".repeat(1000);
+        let sanitised = sanitise(&long, MAX_MESSAGE_LENGTH);
+        assert_eq!(sanitised.chars().count(), MAX_MESSAGE_LENGTH);
+        assert!(sanitised.ends_with('…'));
+
+        let exact = "b".repeat(MAX_MESSAGE_LENGTH);
+        assert_eq!(sanitise(&exact, MAX_MESSAGE_LENGTH), exact);
+
+        // multi-byte characters are not split
+        let emoji = "🙂".repeat(300);
+        assert_eq!(
+            sanitise(&emoji, MAX_MESSAGE_LENGTH).chars().count(),
+            MAX_MESSAGE_LENGTH
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Tells the alert channel what docker does to the server container.
+
+use std::{collections::HashMap, pin::pin, sync::Arc, time::Duration};
+
+use bollard::secret::EventMessage;
+use serenity::{all::Http, futures::StreamExt, utils::MessageBuilder};
+
+use crate::{
+    docker::{self, ContainerStatus},
+    server_state::ServerState,
+};
+
+use super::alerts;
+
+/// Log lines included when the server dies unexpectedly
+const LOG_LINES: usize = 20;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+enum ContainerAlert {
+    Died { exit_code: Option<i64> },
+    OutOfMemory,
+    Health(String),
+    RestartedByPolicy { restart_count: i64 },
+    ImageChanged { image: String },
+}
+
+impl ContainerAlert {
+    fn text(&self) -> String {
+        match self {
+            ContainerAlert::Died {
+                exit_code: Some(exit_code),
+            } => format!(":rotating_light: **Server container died** (exit code {exit_code})"),
+            ContainerAlert::Died { exit_code: None } => {
+                ":rotating_light: **Server container died**".to_string()
+            }
+            ContainerAlert::OutOfMemory => {
+                ":rotating_light: **Server container ran out of memory** and was killed".to_string()
+            }
+            ContainerAlert::Health(status) => {
+                let emoji = match status.as_str() {
+                    "healthy" => ":green_heart:",
+                    "unhealthy" => ":broken_heart:",
+                    _ => ":yellow_heart:",
+                };
+                format!("{emoji} Server container is now {status}")
+            }
+            ContainerAlert::RestartedByPolicy { restart_count } => format!(
+                ":arrows_counterclockwise: Docker restarted the server container (restart #{restart_count})"
+            ),
+            ContainerAlert::ImageChanged { image } => {
+                format!(":package: Server container is now running a new image: {image}")
+            }
+        }
+    }
+}
+
+/// Turns an event into an alert, for the events that do not need a look at the container.
+///
+/// Deaths the bot caused and clean exits, like `stop` typed in the console, are left out.
+fn classify(
+    action: &str,
+    attributes: &HashMap<String, String>,
+    bot_stopping: bool,
+) -> Option<ContainerAlert> {
+    // Older docker versions put the status in the action, like "health_status: healthy"
+    if let Some(status) = action.strip_prefix("health_status") {
+        let status = status.trim_start_matches(':').trim();
+        let status = match status {
+            "" => attributes.get("healthStatus")?.as_str(),
+            status => status,
+        };
+        return Some(ContainerAlert::Health(status.to_string()));
+    }
+
+    match action {
+        "die" if !bot_stopping => {
+            let exit_code = attributes
+                .get("exitCode")
+                .and_then(|code| code.parse().ok());
+            (exit_code != Some(0)).then_some(ContainerAlert::Died { exit_code })
+        }
+        "oom" => Some(ContainerAlert::OutOfMemory),
+        _ => None,
+    }
+}
+
+/// What the container looked like the last time it started.
+#[derive(Debug, Default)]
+struct ContainerWatch {
+    restart_count: Option<i64>,
+    image_id: Option<String>,
+}
+
+impl ContainerWatch {
+    fn on_start(&mut self, status: &ContainerStatus) -> Vec<ContainerAlert> {
+        let mut alerts = Vec::new();
+
+        // Docker only counts the restarts it does itself
+        if self
+            .restart_count
+            .is_some_and(|count| status.restart_count > count)
+        {
+            alerts.push(ContainerAlert::RestartedByPolicy {
+                restart_count: status.restart_count,
+            });
+        }
+        if self.image_id.is_some() && status.image_id != self.image_id {
+            alerts.push(ContainerAlert::ImageChanged {
+                image: status
+                    .image
+                    .clone()
+                    .or_else(|| status.image_id.clone())
+                    .unwrap_or_default(),
+            });
+        }
+
+        self.restart_count = Some(status.restart_count);
+        self.image_id = status.image_id.clone();
+        alerts
+    }
+}
+
+/// Puts the most recent log lines that fit under the alert.
+fn with_logs(text: &str, logs: &[String]) -> String {
+    let lines: Vec<_> = logs.iter().map(|line| line.trim_end()).collect();
+    for skip in 0..lines.len() {
+        let content = MessageBuilder::new()
+            .push_line(text)
+            .push_codeblock_safe(lines[skip..].join("\n"), None)
+            .build();
+        if content.chars().count() <= MAX_MESSAGE_LENGTH {
+            return content;
+        }
+    }
+    text.to_string()
+}
+
+async fn send(server_state: &ServerState, http: &Arc<Http>, alert: ContainerAlert) {
+    let content = match alert {
+        ContainerAlert::Died { .. } => {
+            let (logs, _) = docker::get_logs(server_state, LOG_LINES, None).await;
+            with_logs(&alert.text(), &logs)
+        }
+        _ => alert.text(),
+    };
+
+    let _ = alerts::alert(server_state, http, content)
+        .await
+        .map_err(|e| log::error!("Error in alert: {e}"));
+}
+
+async fn handle_event(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    watch: &mut ContainerWatch,
+    event: EventMessage,
+) {
+    let action = event.action.unwrap_or_default();
+    log::debug!("Docker event for the server container: {action}");
+
+    if action == "start" {
+        match docker::container_status(server_state).await {
+            Ok(status) => {
+                for alert in watch.on_start(&status) {
+                    send(server_state, http, alert).await;
+                }
+            }
+            Err(e) => log::error!("Could not inspect container after it started: {e}"),
+        }
+        return;
+    }
+
+    let attributes = event
+        .actor
+        .and_then(|actor| actor.attributes)
+        .unwrap_or_default();
+    let bot_stopping = server_state.mutables.read().await.lifecycle.bot_stopping();
+    if let Some(alert) = classify(&action, &attributes, bot_stopping) {
+        send(server_state, http, alert).await;
+    }
+}
+
+/// Follows docker events for the server container for as long as the bot runs.
+pub async fn watch(server_state: Arc<ServerState>, http: Arc<Http>) {
+    let mut watch = ContainerWatch::default();
+    match docker::container_status(&server_state).await {
+        Ok(status) => {
+            watch.on_start(&status);
+        }
+        Err(e) => log::error!("Could not inspect container: {e}"),
+    }
+
+    loop {
+        let mut events = pin!(docker::container_events(&server_state));
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) => handle_event(&server_state, &http, &mut watch, event).await,
+                Err(e) => {
+                    log::error!("Docker events stream failed: {e}");
+                    break;
+                }
+            }
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(restart_count: i64, image_id: &str) -> ContainerStatus {
+        ContainerStatus {
+            status: "running".to_string(),
+            running: true,
+            started_at: None,
+            restart_count,
+            health: None,
+            image: Some("itzg/minecraft-server:latest".to_string()),
+            image_id: Some(image_id.to_string()),
+        }
+    }
+
+    #[test]
+    fn classifies_events() {
+        let crashed = HashMap::from([("exitCode".to_string(), "137".to_string())]);
+        let exited = HashMap::from([("exitCode".to_string(), "0".to_string())]);
+        let health = HashMap::from([("healthStatus".to_string(), "unhealthy".to_string())]);
+        let none = HashMap::new();
+
+        assert_eq!(
+            classify("die", &crashed, false),
+            Some(ContainerAlert::Died {
+                exit_code: Some(137)
+            })
+        );
+        // Not knowing how it exited is worth an alert
+        assert_eq!(
+            classify("die", &none, false),
+            Some(ContainerAlert::Died { exit_code: None })
+        );
+        // Someone typed stop in the console
+        assert_eq!(classify("die", &exited, false), None);
+        // The bot stopped or killed it
+        assert_eq!(classify("die", &crashed, true), None);
+
+        // Out of memory is always worth knowing about
+        assert_eq!(
+            classify("oom", &none, false),
+            Some(ContainerAlert::OutOfMemory)
+        );
+        assert_eq!(
+            classify("oom", &none, true),
+            Some(ContainerAlert::OutOfMemory)
+        );
+
+        // Older docker versions put the status in the action
+        assert_eq!(
+            classify("health_status: healthy", &none, false),
+            Some(ContainerAlert::Health("healthy".to_string()))
+        );
+        assert_eq!(
+            classify("health_status", &health, false),
+            Some(ContainerAlert::Health("unhealthy".to_string()))
+        );
+        assert_eq!(classify("health_status", &none, false), None);
+
+        // Everything else is noise
+        assert_eq!(classify("exec_start: rcon-cli list", &none, false), None);
+        assert_eq!(classify("kill", &none, false), None);
+    }
+
+    #[test]
+    fn restarts_and_image_changes() {
+        let mut watch = ContainerWatch::default();
+        assert_eq!(watch.on_start(&status(2, "sha256:a")), vec![]);
+        assert_eq!(watch.on_start(&status(2, "sha256:a")), vec![]);
+        assert_eq!(
+            watch.on_start(&status(3, "sha256:a")),
+            vec![ContainerAlert::RestartedByPolicy { restart_count: 3 }]
+        );
+        // Recreating the container starts the count again
+        assert_eq!(
+            watch.on_start(&status(0, "sha256:b")),
+            vec![ContainerAlert::ImageChanged {
+                image: "itzg/minecraft-server:latest".to_string()
+            }]
+        );
+        assert_eq!(watch.on_start(&status(0, "sha256:b")), vec![]);
+    }
+
+    #[test]
+    fn logs_fit_in_a_message() {
+        let logs = vec!["line one\n".to_string(), "line two\n".to_string()];
+        assert_eq!(
+            with_logs("Server died", &logs),
+            "Server died\n```\nline one\nline two\n```"
+        );
+
+        let logs: Vec<_> = (0..100)
+            .map(|i| format!("{i:03} {}", "x".repeat(50)))
+            .collect();
+        let content = with_logs("Server died", &logs);
+        assert!(content.chars().count() <= MAX_MESSAGE_LENGTH);
+        assert!(content.contains("099 "));
+        assert!(!content.contains("000 "));
+
+        assert_eq!(with_logs("Server died", &[]), "Server died");
+    }
+}
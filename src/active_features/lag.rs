@@ -0,0 +1,243 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use chrono::TimeDelta;
+use serenity::all::Http;
+
+use crate::{commands::format_duration, log_parser::ServerEvent, server_state::ServerState};
+
+use super::alerts;
+
+/// How far back lag warnings count towards an alert
+const WINDOW: Duration = Duration::from_secs(5 * 60);
+/// The server logs the odd warning during autosaves, only alert when it keeps happening
+const MIN_WARNINGS: usize = 3;
+/// Quiet time after the last warning before the server counts as caught up
+const RECOVERY_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+const RECOVERY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq)]
+enum LagAlert {
+    Lagging {
+        warnings: usize,
+        worst_behind: Duration,
+        worst_ticks: u64,
+    },
+    Recovered {
+        lagged_for: Duration,
+    },
+}
+
+impl LagAlert {
+    fn text(&self) -> String {
+        match self {
+            LagAlert::Lagging {
+                warnings,
+                worst_behind,
+                worst_ticks,
+            } => format!(
+                ":snail: **Server is lagging**: {warnings} \"Can't keep up!\" warnings in the last {} minutes, up to {:.1}s ({worst_ticks} ticks) behind",
+                WINDOW.as_secs() / 60,
+                worst_behind.as_secs_f64()
+            ),
+            LagAlert::Recovered { lagged_for } => format!(
+                ":white_check_mark: Server has caught up after lagging for {}",
+                format_duration(TimeDelta::from_std(*lagged_for).unwrap_or_default())
+            ),
+        }
+    }
+}
+
+/// Groups lag warnings so that sustained lag gives one alert, and one more when it is over.
+#[derive(Debug)]
+pub struct LagMonitor {
+    /// Time and size of the warnings inside the window
+    warnings: VecDeque<(SystemTime, Duration, u64)>,
+    lagging_since: Option<SystemTime>,
+    /// Pushed back by every warning, like the cooldowns in [`super::players::PlayerPresenceLog`]
+    recovered_at: SystemTime,
+}
+
+impl LagMonitor {
+    pub fn new() -> Self {
+        Self {
+            warnings: VecDeque::new(),
+            lagging_since: None,
+            recovered_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    fn record(&mut self, at: SystemTime, behind: Duration, ticks: u64) -> Option<LagAlert> {
+        self.warnings.push_back((at, behind, ticks));
+        while self
+            .warnings
+            .front()
+            .is_some_and(|(time, _, _)| *time + WINDOW < at)
+        {
+            self.warnings.pop_front();
+        }
+        self.recovered_at = at + RECOVERY_COOLDOWN;
+
+        if self.lagging_since.is_some() || self.warnings.len() < MIN_WARNINGS {
+            return None;
+        }
+        self.lagging_since = self.warnings.front().map(|(time, _, _)| *time);
+
+        let (_, worst_behind, worst_ticks) = self
+            .warnings
+            .iter()
+            .max_by_key(|(_, behind, _)| *behind)
+            .copied()?;
+        Some(LagAlert::Lagging {
+            warnings: self.warnings.len(),
+            worst_behind,
+            worst_ticks,
+        })
+    }
+
+    fn check_recovered(&mut self, now: SystemTime) -> Option<LagAlert> {
+        let lagging_since = self.lagging_since?;
+        if now < self.recovered_at {
+            return None;
+        }
+
+        self.lagging_since = None;
+        self.warnings.clear();
+        let last_warning = self.recovered_at - RECOVERY_COOLDOWN;
+        Some(LagAlert::Recovered {
+            lagged_for: last_warning
+                .duration_since(lagging_since)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+async fn send(server_state: &ServerState, http: &Arc<Http>, alert: LagAlert) {
+    let _ = alerts::alert(server_state, http, alert.text())
+        .await
+        .map_err(|e| log::error!("Error in alert: {e}"));
+}
+
+pub async fn track_lag(server_state: &ServerState, http: &Arc<Http>, event: &ServerEvent) {
+    let ServerEvent::Lag { behind, ticks } = event else {
+        return;
+    };
+
+    let alert = server_state
+        .mutables
+        .write()
+        .await
+        .lag
+        .record(SystemTime::now(), *behind, *ticks);
+    if let Some(alert) = alert {
+        send(server_state, http, alert).await;
+    }
+}
+
+/// Sends the all clear once the warnings have stopped, which no console line will tell us.
+pub async fn check_recovery_periodically(server_state: Arc<ServerState>, http: Arc<Http>) {
+    let mut interval = tokio::time::interval(RECOVERY_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let alert = server_state
+            .mutables
+            .write()
+            .await
+            .lag
+            .check_recovered(SystemTime::now());
+        if let Some(alert) = alert {
+            send(&server_state, &http, alert).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + secs)
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn alerts_once_when_sustained() {
+        let mut monitor = LagMonitor::new();
+        assert_eq!(monitor.record(at(0), ms(2500), 50), None);
+        assert_eq!(monitor.record(at(60), ms(4000), 80), None);
+        assert_eq!(
+            monitor.record(at(120), ms(3000), 60),
+            Some(LagAlert::Lagging {
+                warnings: 3,
+                worst_behind: ms(4000),
+                worst_ticks: 80,
+            })
+        );
+
+        for secs in [180, 240, 300, 360] {
+            assert_eq!(monitor.record(at(secs), ms(2500), 50), None);
+        }
+    }
+
+    #[test]
+    fn spread_out_warnings_do_not_alert() {
+        let mut monitor = LagMonitor::new();
+        for secs in [0, 200, 400, 600, 800] {
+            assert_eq!(monitor.record(at(secs), ms(2500), 50), None);
+        }
+        assert_eq!(monitor.check_recovered(at(2000)), None);
+    }
+
+    #[test]
+    fn recovers_after_quiet_period() {
+        let mut monitor = LagMonitor::new();
+        for secs in [0, 30, 60, 90] {
+            monitor.record(at(secs), ms(2500), 50);
+        }
+
+        assert_eq!(monitor.check_recovered(at(90)), None);
+        assert_eq!(monitor.check_recovered(at(300)), None);
+        assert_eq!(
+            monitor.check_recovered(at(390)),
+            Some(LagAlert::Recovered {
+                lagged_for: Duration::from_secs(90)
+            })
+        );
+        assert_eq!(monitor.check_recovered(at(400)), None);
+
+        // Lagging again is a new alert
+        assert_eq!(monitor.record(at(1000), ms(2500), 50), None);
+        assert_eq!(monitor.record(at(1010), ms(2500), 50), None);
+        assert!(matches!(
+            monitor.record(at(1020), ms(2500), 50),
+            Some(LagAlert::Lagging { warnings: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn texts() {
+        assert_eq!(
+            LagAlert::Lagging {
+                warnings: 4,
+                worst_behind: ms(2500),
+                worst_ticks: 50
+            }
+            .text(),
+            ":snail: **Server is lagging**: 4 \"Can't keep up!\" warnings in the last 5 minutes, up to 2.5s (50 ticks) behind"
+        );
+        assert_eq!(
+            LagAlert::Recovered {
+                lagged_for: Duration::from_secs(12 * 60)
+            }
+            .text(),
+            ":white_check_mark: Server has caught up after lagging for 12m"
+        );
+    }
+}
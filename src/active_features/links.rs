@@ -0,0 +1,159 @@
+//! Links Discord users to Minecraft accounts once they type a code in game chat.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use rand::Rng;
+use serenity::all::UserId;
+
+use crate::{
+    commands::CommandResult, console, log_parser::ServerEvent, server_state::ServerState,
+    sql::links::AccountLink,
+};
+
+/// Leaves out letters and digits that look alike
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 6;
+pub const CODE_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+pub fn new_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+#[derive(Debug)]
+struct PendingLink {
+    user_id: UserId,
+    code: String,
+    expires_at: SystemTime,
+}
+
+/// Codes handed out by `/link` that nobody has typed in game yet, by lowercase player name.
+#[derive(Debug)]
+pub struct PendingLinks(HashMap<String, PendingLink>);
+
+impl PendingLinks {
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Replaces any code the user or the player already had.
+    pub fn issue(&mut self, user_id: UserId, player_name: &str, code: String, now: SystemTime) {
+        self.0
+            .retain(|_, pending| pending.user_id != user_id && pending.expires_at > now);
+        self.0.insert(
+            player_name.to_lowercase(),
+            PendingLink {
+                user_id,
+                code,
+                expires_at: now + CODE_LIFETIME,
+            },
+        );
+    }
+
+    /// The user waiting on the player, if the message is their code.
+    fn verify(&mut self, player_name: &str, message: &str, now: SystemTime) -> Option<UserId> {
+        let key = player_name.to_lowercase();
+        let pending = self.0.get(&key)?;
+        if pending.expires_at <= now || !pending.code.eq_ignore_ascii_case(message.trim()) {
+            return None;
+        }
+        self.0.remove(&key).map(|pending| pending.user_id)
+    }
+}
+
+pub async fn verify_link(server_state: &ServerState, event: &ServerEvent) -> CommandResult {
+    let ServerEvent::Chat { player, message } = event else {
+        return Ok(());
+    };
+    let Some(user_id) = server_state.mutables.write().await.pending_links.verify(
+        player,
+        message,
+        SystemTime::now(),
+    ) else {
+        return Ok(());
+    };
+
+    // Together, so a failed insert does not leave the player without their old link
+    let mut transaction = server_state.db.begin().await?;
+    AccountLink::remove_player(player)
+        .execute(&mut *transaction)
+        .await?;
+    AccountLink::insert(user_id, player)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    log::info!("Linked {player} to Discord user {user_id}");
+
+    if let Err(e) = console::send_command(
+        server_state,
+        &format!("tell {player} Your account is now linked to Discord"),
+    )
+    .await
+    {
+        log::warn!("Could not tell {player} their account is linked: {e}");
+    }
+
+    Ok(())
+}
+
+/// The player's name with a mention of their Discord account, if they linked one.
+pub async fn with_mention(server_state: &ServerState, player_name: &str) -> String {
+    match AccountLink::get_for_player(&server_state.db, player_name).await {
+        Ok(Some(link)) => format!("{player_name} (<@{}>)", link.discord_id.get()),
+        Ok(None) => player_name.to_string(),
+        Err(e) => {
+            log::error!("Could not look up the Discord account of {player_name}: {e}");
+            player_name.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + secs)
+    }
+
+    #[test]
+    fn codes() {
+        let code = new_code();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|c| CODE_ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    fn verifies_code_from_the_right_player() {
+        let user = UserId::new(1);
+        let mut pending = PendingLinks::new();
+        pending.issue(user, "Sally", "ABC234".to_string(), at(0));
+
+        assert_eq!(pending.verify("bob", "ABC234", at(10)), None);
+        assert_eq!(pending.verify("sally", "hello", at(10)), None);
+        assert_eq!(pending.verify("sally", " abc234 ", at(10)), Some(user));
+        // Codes only work once
+        assert_eq!(pending.verify("sally", "ABC234", at(20)), None);
+    }
+
+    #[test]
+    fn codes_expire_and_are_replaced() {
+        let user = UserId::new(1);
+        let mut pending = PendingLinks::new();
+        pending.issue(user, "sally", "ABC234".to_string(), at(0));
+        assert_eq!(
+            pending.verify("sally", "ABC234", at(CODE_LIFETIME.as_secs())),
+            None
+        );
+
+        pending.issue(user, "sally", "ABC234".to_string(), at(1000));
+        pending.issue(user, "sally_alt", "XYZ789".to_string(), at(1010));
+        assert_eq!(pending.verify("sally", "ABC234", at(1020)), None);
+        assert_eq!(pending.verify("sally_alt", "XYZ789", at(1020)), Some(user));
+    }
+}
@@ -1 +1,47 @@
+use std::sync::Arc;
+
+use serenity::all::Http;
+
+use crate::{lifecycle, log_parser::ServerEvent, server_state::ServerState};
+
+pub mod alerts;
+pub mod announcements;
+pub mod chat_bridge;
+pub mod container_events;
+pub mod lag;
+pub mod links;
 pub mod players;
+pub mod presence;
+pub mod sessions;
+pub mod stats;
+pub mod whitelist;
+
+/// Runs every feature that reacts to lines from the server console.
+pub async fn handle_event(server_state: &Arc<ServerState>, http: &Arc<Http>, event: &ServerEvent) {
+    lifecycle::update_from_event(server_state, event).await;
+    players::track_online_players(server_state, event).await;
+    presence::update_from_event(server_state, event).await;
+    lag::track_lag(server_state, http, event).await;
+    let _ = sessions::record_session(server_state, event)
+        .await
+        .map_err(|e| log::error!("Error in record_session: {e}"));
+
+    let _ = players::snitch_player_joined(server_state, http, event)
+        .await
+        .map_err(|e| log::error!("Error in snitch_player_joined: {e}"));
+    let _ = players::snitch_player_left(server_state, http, event)
+        .await
+        .map_err(|e| log::error!("Error in snitch_player_left: {e}"));
+    let _ = announcements::announce_death(server_state, http, event)
+        .await
+        .map_err(|e| log::error!("Error in announce_death: {e}"));
+    let _ = announcements::announce_advancement(server_state, http, event)
+        .await
+        .map_err(|e| log::error!("Error in announce_advancement: {e}"));
+    let _ = links::verify_link(server_state, event)
+        .await
+        .map_err(|e| log::error!("Error in verify_link: {e}"));
+    let _ = chat_bridge::relay_to_discord(server_state, http, event)
+        .await
+        .map_err(|e| log::error!("Error in relay_to_discord: {e}"));
+}
@@ -1,6 +1,6 @@
 use crate::commands::CommandResult;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         Arc,
         atomic::{AtomicU8, Ordering},
@@ -8,13 +8,16 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use serenity::all::{CreateMessage, Http};
+use serenity::all::Http;
 
 use crate::{
+    log_parser::{LeaveReason, ServerEvent},
     server_state::ServerState,
-    sql::{self, player_join::PlayerJoinServerChannel},
+    sql::{self, subscriptions::EventType},
 };
 
+use super::{announcements::announce, links};
+
 #[derive(Debug)]
 pub struct PlayerPresenceLog(HashMap<String, SystemTime>);
 
@@ -60,16 +63,64 @@ impl PlayerPresenceLog {
     }
 }
 
+/// Players currently on the server, as seen in the join and leave messages.
+#[derive(Debug)]
+pub struct OnlinePlayers(HashSet<String>);
+
+impl OnlinePlayers {
+    pub fn new() -> Self {
+        Self(HashSet::new())
+    }
+
+    pub fn update(&mut self, event: &ServerEvent) {
+        match event {
+            ServerEvent::Join { player } => {
+                self.0.insert(player.clone());
+            }
+            ServerEvent::Leave { player, .. } => {
+                self.0.remove(player);
+            }
+            // Nobody survives a restart
+            ServerEvent::ServerStarting { .. } | ServerEvent::ServerStopping => self.0.clear(),
+            _ => {}
+        }
+    }
+
+    pub fn set(&mut self, names: impl IntoIterator<Item = String>) {
+        self.0 = names.into_iter().collect();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.0.iter().map(String::as_str).collect();
+        names.sort_unstable_by_key(|name| name.to_lowercase());
+        names
+    }
+}
+
+pub async fn track_online_players(server_state: &Arc<ServerState>, event: &ServerEvent) {
+    let online_players = &mut server_state.mutables.write().await.online_players;
+    let count = online_players.len();
+    online_players.update(event);
+    if online_players.len() != count {
+        log::debug!("Players online: {:?}", online_players.names());
+    }
+}
+
+const PLAYER_ANNOUNCE_COOLDOWN: Duration = Duration::new(10 * 60, 0);
+
 pub async fn snitch_player_joined(
     server_state: &Arc<ServerState>,
     http: &Arc<Http>,
-    message: &str,
+    event: &ServerEvent,
 ) -> CommandResult {
-    const PLAYER_ANNOUNCE_COOLDOWN: Duration = Duration::new(10 * 60, 0);
-
-    let Some(player_name) = player_joined_catch(message) else {
+    let ServerEvent::Join { player } = event else {
         return Ok(());
     };
+    let player_name = player.as_str();
 
     if !server_state
         .mutables
@@ -88,95 +139,146 @@ pub async fn snitch_player_joined(
         .player_presence_log
         .new_player_now(player_name.to_string(), PLAYER_ANNOUNCE_COOLDOWN);
 
+    if is_ignored(server_state, player_name).await {
+        return Ok(());
+    }
+
+    let player = links::with_mention(server_state, player_name).await;
+    announce(
+        server_state,
+        http,
+        EventType::Join,
+        format!("{player} just joined the server!"),
+    )
+    .await
+}
+
+pub async fn snitch_player_left(
+    server_state: &Arc<ServerState>,
+    http: &Arc<Http>,
+    event: &ServerEvent,
+) -> CommandResult {
+    let ServerEvent::Leave { player, reason } = event else {
+        return Ok(());
+    };
+    let player_name = player.as_str();
+
+    // Vanilla logs "lost connection" and then "left the game" for the same disconnect
+    if !server_state
+        .mutables
+        .read()
+        .await
+        .player_leave_log
+        .is_record_expired(player_name)
+    {
+        log::trace!("{player_name} has left again before cooldown expiry. Ignoring.");
+        return Ok(());
+    }
+    server_state
+        .mutables
+        .write()
+        .await
+        .player_leave_log
+        .new_player_now(player_name.to_string(), PLAYER_ANNOUNCE_COOLDOWN);
+
+    if is_ignored(server_state, player_name).await {
+        return Ok(());
+    }
+
+    let player = links::with_mention(server_state, player_name).await;
+    let content = match reason {
+        LeaveReason::Left => format!("{player} just left the server!"),
+        LeaveReason::LostConnection(reason) => {
+            format!("{player} just left the server! ({reason})")
+        }
+    };
+    announce(server_state, http, EventType::Leave, content).await
+}
+
+async fn is_ignored(server_state: &ServerState, player_name: &str) -> bool {
     match sql::player_join::PlayerJoinIgnore::has_player(&server_state.db, player_name).await {
         Ok(ignore_player) => {
             if ignore_player {
                 log::trace!("{player_name} in ignore list. Not sending message.");
-                return Ok(());
             }
+            ignore_player
         }
         Err(e) => {
             log::error!("DB Error: {e}");
-            return Ok(());
+            true
         }
     }
-
-    for PlayerJoinServerChannel {
-        channel_id,
-        guild_id: _,
-    } in PlayerJoinServerChannel::get_all_channels(&server_state.db)
-        .await
-        .expect("The db should respond")
-    {
-        log::debug!(
-            "Sending message in channel {} that {player_name} just joined.",
-            channel_id.get()
-        );
-        serenity::all::ChannelId::new(channel_id.get())
-            .send_message(
-                &http,
-                CreateMessage::new().content(format!("{player_name} just joined the server!")),
-            )
-            .await?;
-    }
-
-    Ok(())
-}
-
-pub fn player_joined_catch(s: &str) -> Option<&str> {
-    // Check if chat message
-    if s.contains("]: <") {
-        return None;
-    }
-
-    // Returns if suffix doesnt exist
-    let s = s.strip_suffix(" joined the game")?;
-    let name_start = s.find("]: ")? + 3;
-    let name = &s[name_start..];
-    Some(name)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::active_features::players::player_joined_catch;
+    use crate::log_parser::{LeaveReason, ServerEvent};
+
+    use super::OnlinePlayers;
 
     #[test]
-    fn should_match() {
-        // Normal name
-        let s = "[20:41:25 INFO]: sally joined the game";
-        assert_eq!(player_joined_catch(s), Some("sally"));
+    fn tracks_joins_and_leaves() {
+        let mut online = OnlinePlayers::new();
+        online.update(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        online.update(&ServerEvent::Join {
+            player: "bob".to_string(),
+        });
+        online.update(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        assert_eq!(online.names(), vec!["bob", "sally"]);
 
-        // normal with caps
-        let s = "[20:41:25 INFO]: Sally joined the game";
-        assert_eq!(player_joined_catch(s), Some("Sally"));
+        online.update(&ServerEvent::Leave {
+            player: "sally".to_string(),
+            reason: LeaveReason::Left,
+        });
+        assert_eq!(online.names(), vec!["bob"]);
 
-        // Multi word name
-        let s = "[20:41:25 INFO]: Sally Whiller joined the game";
-        assert_eq!(player_joined_catch(s), Some("Sally Whiller"));
+        online.update(&ServerEvent::Leave {
+            player: "bob".to_string(),
+            reason: LeaveReason::LostConnection("Timed out".to_string()),
+        });
+        // "left the game" follows "lost connection"
+        online.update(&ServerEvent::Leave {
+            player: "bob".to_string(),
+            reason: LeaveReason::Left,
+        });
+        assert_eq!(online.len(), 0);
+    }
 
-        // Single character name
-        let s = "[20:41:25 INFO]: a joined the game";
-        assert_eq!(player_joined_catch(s), Some("a"));
+    #[test]
+    fn clears_on_server_lifecycle() {
+        let mut online = OnlinePlayers::new();
+        online.update(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        online.update(&ServerEvent::ServerStopping);
+        assert_eq!(online.len(), 0);
 
-        // just a space
-        let s = "[20:41:25 INFO]:   joined the game";
-        assert_eq!(player_joined_catch(s), Some(" "));
+        online.update(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        online.update(&ServerEvent::ServerStarting {
+            version: "1.21.10".to_string(),
+        });
+        assert_eq!(online.len(), 0);
     }
 
     #[test]
-    fn no_match() {
-        // chat messages
-        assert_eq!(
-            player_joined_catch("[13:18:57 INFO]: <BalloonsAndPeople> Wiliam joined the game"),
-            None
-        );
-        assert_eq!(
-            player_joined_catch("[13:18:57 INFO]: <John> someone joined the game"),
-            None
-        );
-        assert_eq!(
-            player_joined_catch("[13:18:57 INFO]: <Alice> I joined the game yesterday"),
-            None
-        );
+    fn ignores_other_events() {
+        let mut online = OnlinePlayers::new();
+        online.update(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        online.update(&ServerEvent::Chat {
+            player: "bob".to_string(),
+            message: "hi".to_string(),
+        });
+        online.update(&ServerEvent::Unknown {
+            message: "bob joined the game".to_string(),
+        });
+        assert_eq!(online.names(), vec!["sally"]);
     }
 }
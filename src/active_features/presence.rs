@@ -0,0 +1,218 @@
+use std::{sync::Arc, time::Duration};
+
+use serenity::all::{ActivityData, OnlineStatus};
+
+use crate::{log_parser::ServerEvent, server_ping, server_state::ServerState};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the bot shows as its own Discord status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerPresence {
+    Online {
+        players: usize,
+        max_players: Option<u64>,
+    },
+    Offline,
+    Restarting,
+}
+
+impl ServerPresence {
+    fn text(&self) -> String {
+        match self {
+            ServerPresence::Online {
+                players,
+                max_players: Some(max_players),
+            } => format!("{players}/{max_players} online"),
+            ServerPresence::Online {
+                players,
+                max_players: None,
+            } => format!("{players} online"),
+            ServerPresence::Offline => "Server offline".to_string(),
+            ServerPresence::Restarting => "Restarting...".to_string(),
+        }
+    }
+
+    fn activity(&self) -> (ActivityData, OnlineStatus) {
+        match self {
+            ServerPresence::Online { .. } => {
+                (ActivityData::playing(self.text()), OnlineStatus::Online)
+            }
+            ServerPresence::Offline => (
+                ActivityData::custom(self.text()),
+                OnlineStatus::DoNotDisturb,
+            ),
+            ServerPresence::Restarting => (ActivityData::custom(self.text()), OnlineStatus::Idle),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PresenceState {
+    server_online: bool,
+    restarting: bool,
+    players: usize,
+    max_players: Option<u64>,
+    last_sent: Option<ServerPresence>,
+}
+
+impl PresenceState {
+    pub fn new() -> Self {
+        Self {
+            server_online: false,
+            restarting: false,
+            players: 0,
+            max_players: None,
+            last_sent: None,
+        }
+    }
+
+    fn presence(&self) -> ServerPresence {
+        if self.restarting {
+            ServerPresence::Restarting
+        } else if self.server_online {
+            ServerPresence::Online {
+                players: self.players,
+                max_players: self.max_players,
+            }
+        } else {
+            ServerPresence::Offline
+        }
+    }
+}
+
+/// Sends the presence to Discord, unless it has not changed since the last time.
+async fn publish(server_state: &ServerState) {
+    let runners = server_state.shard_manager.runners.lock().await;
+    // Shards that are not up yet get the presence on the next refresh
+    if runners.is_empty() {
+        return;
+    }
+
+    let presence = {
+        let mut mutables = server_state.mutables.write().await;
+        let presence = mutables.presence.presence();
+        if mutables.presence.last_sent.as_ref() == Some(&presence) {
+            return;
+        }
+        mutables.presence.last_sent = Some(presence.clone());
+        presence
+    };
+
+    log::debug!("Setting presence: {}", presence.text());
+    let (activity, status) = presence.activity();
+    for runner in runners.values() {
+        runner
+            .runner_tx
+            .set_presence(Some(activity.clone()), status);
+    }
+}
+
+pub async fn update_from_event(server_state: &ServerState, event: &ServerEvent) {
+    {
+        let mut mutables = server_state.mutables.write().await;
+        match event {
+            ServerEvent::Join { .. } | ServerEvent::Leave { .. } => {
+                mutables.presence.server_online = true;
+                mutables.presence.players = mutables.online_players.len();
+            }
+            ServerEvent::ServerStarted { .. } => {
+                mutables.presence.server_online = true;
+                mutables.presence.players = 0;
+            }
+            ServerEvent::ServerStopping => mutables.presence.server_online = false,
+            _ => return,
+        }
+    }
+
+    publish(server_state).await;
+}
+
+pub async fn set_restarting(server_state: &ServerState, restarting: bool) {
+    {
+        let presence = &mut server_state.mutables.write().await.presence;
+        presence.restarting = restarting;
+        // Stays offline until the server logs that it is done starting
+        presence.server_online = false;
+    }
+
+    publish(server_state).await;
+}
+
+/// Pings the server on a timer, which also picks up the max player count.
+pub async fn refresh_periodically(server_state: Arc<ServerState>) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let status = server_ping::ping(&server_state.bot_config.server_address).await;
+        {
+            let mutables = &mut *server_state.mutables.write().await;
+            match status {
+                Ok(status) => {
+                    mutables.presence.server_online = true;
+                    mutables.presence.players = status.online_players as usize;
+                    mutables.presence.max_players = Some(status.max_players);
+                    // Catches up on players that joined while the bot was not running
+                    if status.player_sample.len() as u64 == status.online_players {
+                        mutables.online_players.set(status.player_sample);
+                    }
+                }
+                Err(e) => {
+                    log::debug!("Could not ping server: {e}");
+                    mutables.presence.server_online = false;
+                }
+            }
+        }
+
+        publish(&server_state).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PresenceState, ServerPresence};
+
+    #[test]
+    fn texts() {
+        // Online with a known limit
+        let presence = ServerPresence::Online {
+            players: 3,
+            max_players: Some(20),
+        };
+        assert_eq!(presence.text(), "3/20 online");
+
+        // Online before the limit is known
+        let presence = ServerPresence::Online {
+            players: 0,
+            max_players: None,
+        };
+        assert_eq!(presence.text(), "0 online");
+
+        // Offline
+        assert_eq!(ServerPresence::Offline.text(), "Server offline");
+
+        // Restarting
+        assert_eq!(ServerPresence::Restarting.text(), "Restarting...");
+    }
+
+    #[test]
+    fn restarting_takes_priority() {
+        let mut state = PresenceState::new();
+        assert_eq!(state.presence(), ServerPresence::Offline);
+
+        state.server_online = true;
+        state.players = 2;
+        state.max_players = Some(10);
+        assert_eq!(
+            state.presence(),
+            ServerPresence::Online {
+                players: 2,
+                max_players: Some(10)
+            }
+        );
+
+        state.restarting = true;
+        assert_eq!(state.presence(), ServerPresence::Restarting);
+    }
+}
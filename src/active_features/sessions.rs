@@ -0,0 +1,39 @@
+use chrono::Utc;
+
+use crate::{
+    log_parser::{LeaveReason, ServerEvent},
+    server_state::ServerState,
+    sql::sessions::PlaySession,
+};
+
+pub async fn record_session(server_state: &ServerState, event: &ServerEvent) -> sqlx::Result<()> {
+    let now = Utc::now();
+    match event {
+        ServerEvent::Join { player } => {
+            // A missed leave message would otherwise leave two sessions open
+            PlaySession::end(player, now, "rejoined")
+                .execute(&server_state.db)
+                .await?;
+            PlaySession::start(player, now)
+                .execute(&server_state.db)
+                .await?;
+        }
+        ServerEvent::Leave { player, reason } => {
+            let reason = match reason {
+                LeaveReason::Left => "left".to_string(),
+                LeaveReason::LostConnection(reason) => format!("lost connection: {reason}"),
+            };
+            PlaySession::end(player, now, &reason)
+                .execute(&server_state.db)
+                .await?;
+        }
+        ServerEvent::ServerStarting { .. } | ServerEvent::ServerStopping => {
+            PlaySession::end_all(now, "server stopped")
+                .execute(&server_state.db)
+                .await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{TimeDelta, Utc};
+
+use crate::{
+    docker, lifecycle::ServerLifecycle, server_state::ServerState, sql::stats::StatsSample,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// `/stats` looks back a day at most, keep a bit more in case that changes
+const RETENTION: TimeDelta = TimeDelta::days(7);
+
+/// Samples the container's resource usage on a timer and keeps a rolling history.
+pub async fn record_periodically(server_state: Arc<ServerState>) {
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let lifecycle = server_state.mutables.read().await.lifecycle.state();
+        if matches!(
+            lifecycle,
+            ServerLifecycle::Stopped | ServerLifecycle::Crashed
+        ) {
+            continue;
+        }
+
+        let stats = match docker::container_stats(&server_state).await {
+            Ok(Some(stats)) => stats,
+            Ok(None) => continue,
+            Err(e) => {
+                log::warn!("Could not get container stats: {e}");
+                continue;
+            }
+        };
+
+        let now = Utc::now();
+        let _ = StatsSample::new(&stats, now)
+            .insert()
+            .execute(&server_state.db)
+            .await
+            .map_err(|e| log::error!("Error in record stats: {e}"));
+        let _ = StatsSample::delete_before(now - RETENTION)
+            .execute(&server_state.db)
+            .await
+            .map_err(|e| log::error!("Error in prune stats: {e}"));
+    }
+}
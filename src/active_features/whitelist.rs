@@ -0,0 +1,34 @@
+use serenity::all::{GuildId, User};
+
+use crate::{
+    commands::CommandResult, console, server_state::ServerState, sql::whitelist::WhitelistEntry,
+};
+
+/// Takes the players a member whitelisted off the whitelist when they leave the guild.
+pub async fn remove_departed_member(
+    server_state: &ServerState,
+    guild_id: GuildId,
+    user: &User,
+) -> CommandResult {
+    if !server_state.bot_config.whitelist_remove_on_leave {
+        return Ok(());
+    }
+
+    for name in WhitelistEntry::get_names_for_user(&server_state.db, guild_id, user.id).await? {
+        log::info!(
+            "{} left the guild, removing {name} from the whitelist",
+            user.name
+        );
+        if let Err(e) =
+            console::send_command(server_state, &format!("whitelist remove {name}")).await
+        {
+            log::error!("Could not remove {name} from the whitelist: {e}");
+            continue;
+        }
+        WhitelistEntry::remove(guild_id, &name)
+            .execute(&server_state.db)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,778 @@
+//! World backups, copied out of the container while the server is told not to write to disk.
+//!
+//! Backups go into a `ChunkStore` in BACKUP_DIR, so each one only takes up the space of what
+//! changed since the ones before it. Backups from before the store are a directory of tar
+//! archives, which can still be restored.
+
+use std::{collections::HashSet, path::Path, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, Utc};
+use serenity::{
+    all::Http,
+    all::UserId,
+    futures::{StreamExt, stream::BoxStream},
+};
+use thiserror::Error;
+use tokio::sync::{
+    MutexGuard,
+    broadcast::{self, error::RecvError},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    active_features::alerts,
+    chunk_store::{ChunkStore, Snapshot, Verification},
+    console::{self, ConsoleError},
+    docker, lifecycle,
+    lifecycle::{LifecycleError, ServerLifecycle},
+    log_parser::{self, ServerEvent},
+    offsite,
+    server_state::ServerState,
+    sql::backups::BackupRecord,
+};
+
+/// A big world can take a while to flush
+const SAVE_TIMEOUT: Duration = Duration::from_secs(120);
+/// How long a restored world gets to reach "Done" before the old one is put back
+const RESTORE_START_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Backups are named after when they were made, like `20261018-120000`
+pub const NAME_FORMAT: &str = "%Y%m%d-%H%M%S";
+/// Where the current world is kept while a backup is restored over it
+const SET_ASIDE_SUFFIX: &str = ".pre-restore";
+/// What the set aside script exits with when a previous restore left a directory behind
+const LEFTOVER_EXIT_CODE: i64 = 3;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Backups are not set up, BACKUP_DIR is missing")]
+    Disabled,
+    #[error("Another backup is already running")]
+    Busy,
+    #[error("Timed out waiting for the server to save the world")]
+    SaveTimeout,
+    #[error("None of the world directories exist in the container")]
+    NothingToBackUp,
+    #[error("The files of backup {0} are missing")]
+    MissingFiles(String),
+    #[error(
+        "Moving the world directories failed with exit code {0}, check for leftover {SET_ASIDE_SUFFIX} directories"
+    )]
+    Helper(i64),
+    #[error(
+        "A {SET_ASIDE_SUFFIX} directory from an earlier restore is still there, move or delete it first"
+    )]
+    Leftover,
+    #[error("The server stopped while starting")]
+    StartFailed,
+    #[error("The server did not finish starting in time")]
+    StartTimeout,
+    #[error("{0}\nThe previous world was put back")]
+    RolledBack(String),
+    #[error("{reason}\nPutting the previous world back failed too: {error}")]
+    RollbackFailed { reason: String, error: String },
+    #[error("Console error: {0}")]
+    Console(#[from] ConsoleError),
+    #[error("{0}")]
+    Lifecycle(#[from] LifecycleError),
+    #[error("Docker error: {0}")]
+    Docker(#[from] bollard::errors::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Db(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupTrigger {
+    Manual,
+    Scheduled,
+}
+
+impl BackupTrigger {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackupTrigger::Manual => "manual",
+            BackupTrigger::Scheduled => "scheduled",
+        }
+    }
+}
+
+/// The archive a world directory is stored as, like `world_nether.tar` for `/data/world_nether`.
+pub fn archive_name(path: &str) -> String {
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    format!("{name}.tar")
+}
+
+fn is_saved(line: &str) -> bool {
+    matches!(
+        log_parser::parse(line).event,
+        ServerEvent::CommandOutput { message } if message.starts_with("Saved the game")
+    )
+}
+
+/// Turns off autosave and waits for everything in memory to be written out.
+async fn flush_world(server_state: &ServerState) -> Result<(), BackupError> {
+    let mut lines = server_state.console_lines.subscribe();
+    console::send_command(server_state, "save-off").await?;
+    let output = console::send_command(server_state, "save-all flush").await?;
+    // RCON answers once the save is done, the console only logs it
+    if output.is_some_and(|output| output.contains("Saved the game")) {
+        return Ok(());
+    }
+
+    tokio::time::timeout(SAVE_TIMEOUT, wait_for_save(&mut lines))
+        .await
+        .map_err(|_| BackupError::SaveTimeout)?
+}
+
+async fn wait_for_save(lines: &mut broadcast::Receiver<String>) -> Result<(), BackupError> {
+    loop {
+        match lines.recv().await {
+            Ok(line) if is_saved(&line) => return Ok(()),
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return Err(BackupError::SaveTimeout),
+        }
+    }
+}
+
+/// Chunks each world directory into the store as a tar archive, returning the snapshot and how
+/// many bytes were new to the store.
+async fn copy_world(
+    server_state: &ServerState,
+    store: &ChunkStore,
+) -> Result<(Snapshot, u64), BackupError> {
+    let mut snapshot = Snapshot::default();
+    let mut new_bytes = 0;
+    for path in &server_state.bot_config.backup_paths {
+        let mut writer = store.write_archive(&archive_name(path));
+        let mut archive = docker::download_archive(server_state, path);
+        let mut found = true;
+        while let Some(chunk) = archive.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                // Vanilla keeps the nether and the end inside the world directory
+                Err(e) if docker::is_not_found(&e) => {
+                    log::debug!("{path} is not in the container, not backing it up");
+                    found = false;
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            writer.write(&chunk).await?;
+        }
+
+        if found {
+            let (archive, new) = writer.finish().await?;
+            snapshot.archives.push(archive);
+            new_bytes += new;
+        }
+    }
+
+    match snapshot.archives.len() {
+        0 => Err(BackupError::NothingToBackUp),
+        _ => Ok((snapshot, new_bytes)),
+    }
+}
+
+/// Makes sure only one backup runs at a time, even when the schedule and a user collide.
+///
+/// The lock is let go when the guard is dropped, so a backup that fails or is cancelled halfway
+/// does not block the ones after it.
+fn lock(server_state: &ServerState) -> Result<MutexGuard<'_, ()>, BackupError> {
+    server_state
+        .backup_lock
+        .try_lock()
+        .map_err(|_| BackupError::Busy)
+}
+
+pub async fn create(
+    server_state: &ServerState,
+    trigger: BackupTrigger,
+    created_by: Option<UserId>,
+) -> Result<BackupRecord, BackupError> {
+    let backup_dir = server_state
+        .bot_config
+        .backup_dir
+        .as_ref()
+        .ok_or(BackupError::Disabled)?;
+    let _lock = lock(server_state)?;
+    create_locked(server_state, backup_dir, trigger, created_by).await
+}
+
+async fn create_locked(
+    server_state: &ServerState,
+    backup_dir: &Path,
+    trigger: BackupTrigger,
+    created_by: Option<UserId>,
+) -> Result<BackupRecord, BackupError> {
+    let created_at = Utc::now();
+    let name = created_at.format(NAME_FORMAT).to_string();
+    let store = ChunkStore::new(backup_dir);
+    log::info!("Backing up the world as {name}");
+
+    // A stopped server has nothing in memory, and no console to talk to
+    let running = lifecycle::state(server_state).await == ServerLifecycle::Running;
+    let result = async {
+        if running {
+            flush_world(server_state).await?;
+        }
+        copy_world(server_state, &store).await
+    }
+    .await;
+    if running && let Err(e) = console::send_command(server_state, "save-on").await {
+        log::error!("Could not turn autosave back on after the backup: {e}");
+    }
+
+    // Chunks written by a failed backup are collected with the next prune
+    let (snapshot, new_bytes) = result?;
+    store.save_snapshot(&name, &snapshot).await?;
+    log::info!(
+        "Backed up {} bytes, {new_bytes} of them new",
+        snapshot.size()
+    );
+
+    let mut record = BackupRecord {
+        id: 0,
+        created_at,
+        name,
+        size_bytes: snapshot.size() as i64,
+        stored_bytes: new_bytes as i64,
+        trigger: trigger.name().to_string(),
+        created_by: created_by.map(|user_id| user_id.get().into()),
+    };
+    record.id = record.insert(&server_state.db).await?;
+
+    prune(server_state, backup_dir).await?;
+    Ok(record)
+}
+
+/// Single quotes a path for `sh`.
+fn shell_quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+/// The directory a world directory gets extracted into, like `/data` for `/data/world`.
+fn parent_dir(path: &str) -> &str {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+fn set_aside_script(paths: &[String]) -> String {
+    let mut script = vec!["set -e".to_string()];
+    // Checked for every path before anything moves, so every set aside directory a rollback
+    // finds afterwards is one this restore made
+    for path in paths {
+        let aside = shell_quote(&format!("{path}{SET_ASIDE_SUFFIX}"));
+        script.push(format!(
+            "if [ -e {aside} ]; then exit {LEFTOVER_EXIT_CODE}; fi"
+        ));
+    }
+    for path in paths {
+        let aside = shell_quote(&format!("{path}{SET_ASIDE_SUFFIX}"));
+        let path = shell_quote(path);
+        script.push(format!("if [ -e {path} ]; then mv {path} {aside}; fi"));
+    }
+    script.join("\n")
+}
+
+/// Puts the set aside directories back.
+///
+/// Once the whole set aside script ran, a path without a set aside copy did not exist before
+/// the restore, so whatever the restore extracted there is deleted too.
+fn roll_back_script(paths: &[String], set_aside: bool) -> String {
+    let mut script = vec!["set -e".to_string()];
+    for path in paths {
+        let aside = shell_quote(&format!("{path}{SET_ASIDE_SUFFIX}"));
+        let path = shell_quote(path);
+        script.push(if set_aside {
+            format!(
+                "if [ -e {aside} ]; then rm -rf {path}; mv {aside} {path}; else rm -rf {path}; fi"
+            )
+        } else {
+            format!("if [ -e {aside} ]; then rm -rf {path}; mv {aside} {path}; fi")
+        });
+    }
+    script.join("\n")
+}
+
+fn clean_up_script(paths: &[String]) -> String {
+    let asides: Vec<_> = paths
+        .iter()
+        .map(|path| shell_quote(&format!("{path}{SET_ASIDE_SUFFIX}")))
+        .collect();
+    format!("rm -rf {}", asides.join(" "))
+}
+
+async fn run_script(server_state: &ServerState, script: &str) -> Result<(), BackupError> {
+    match docker::run_in_volumes(server_state, script).await? {
+        0 => Ok(()),
+        LEFTOVER_EXIT_CODE => Err(BackupError::Leftover),
+        code => Err(BackupError::Helper(code)),
+    }
+}
+
+/// Replaces the world with a backup, putting the old one back if the server will not start.
+pub async fn restore(server_state: &ServerState, backup: &BackupRecord) -> Result<(), BackupError> {
+    let backup_dir = server_state
+        .bot_config
+        .backup_dir
+        .as_ref()
+        .ok_or(BackupError::Disabled)?;
+    let _lock = lock(server_state)?;
+    restore_locked(server_state, backup_dir, &backup.name).await
+}
+
+/// The archive of a world directory in a backup, from the chunk store or an old tar backup.
+async fn open_archive(
+    backup_dir: &Path,
+    snapshot: Option<&Snapshot>,
+    name: &str,
+    archive: &str,
+) -> Result<Option<BoxStream<'static, std::io::Result<bytes::Bytes>>>, BackupError> {
+    if let Some(snapshot) = snapshot {
+        return Ok(snapshot
+            .archive(archive)
+            .map(|archive| ChunkStore::new(backup_dir).read_archive(archive).boxed()));
+    }
+    match tokio::fs::File::open(backup_dir.join(name).join(archive)).await {
+        Ok(file) => Ok(Some(ReaderStream::new(file).boxed())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn restore_locked(
+    server_state: &ServerState,
+    backup_dir: &Path,
+    name: &str,
+) -> Result<(), BackupError> {
+    let paths = &server_state.bot_config.backup_paths;
+    let snapshot = ChunkStore::new(backup_dir).load_snapshot(name).await?;
+    let mut archives = Vec::new();
+    for path in paths {
+        let archive =
+            open_archive(backup_dir, snapshot.as_ref(), name, &archive_name(path)).await?;
+        if let Some(archive) = archive {
+            archives.push((parent_dir(path), archive));
+        }
+    }
+    if archives.is_empty() {
+        return Err(BackupError::MissingFiles(name.to_string()));
+    }
+
+    log::info!("Restoring the world from {name}");
+    let restore = lifecycle::begin_restore(server_state).await?;
+    let result = restore_held(server_state, &restore, paths, archives).await;
+    restore.finish().await;
+    result
+}
+
+async fn restore_held(
+    server_state: &ServerState,
+    restore: &lifecycle::Restore<'_>,
+    paths: &[String],
+    archives: Vec<(&str, BoxStream<'static, std::io::Result<bytes::Bytes>>)>,
+) -> Result<(), BackupError> {
+    let was_running = matches!(
+        lifecycle::state(server_state).await,
+        ServerLifecycle::Running | ServerLifecycle::Starting
+    );
+    if was_running {
+        restore.stop().await?;
+    }
+
+    match run_script(server_state, &set_aside_script(paths)).await {
+        Ok(()) => {}
+        // Nothing was moved, and the leftover may be the only copy of an older world
+        Err(BackupError::Leftover) => {
+            if was_running && let Err(e) = restore.start().await {
+                log::error!("Could not start the server again after refusing the restore: {e}");
+            }
+            return Err(BackupError::Leftover);
+        }
+        Err(e) => return Err(roll_back(server_state, restore, false, e.to_string()).await),
+    }
+
+    let result = async {
+        for (parent, archive) in archives {
+            docker::upload_archive(server_state, parent, archive).await?;
+        }
+        start_and_wait(server_state, restore).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            // The restored world is up, a leftover copy only costs disk space
+            if let Err(e) = run_script(server_state, &clean_up_script(paths)).await {
+                log::warn!("Could not delete the world from before the restore: {e}");
+            }
+            Ok(())
+        }
+        Err(e) => Err(roll_back(server_state, restore, true, e.to_string()).await),
+    }
+}
+
+/// Starts the server and waits for it to finish loading the world.
+async fn start_and_wait(
+    server_state: &ServerState,
+    restore: &lifecycle::Restore<'_>,
+) -> Result<(), BackupError> {
+    restore.start().await?;
+    tokio::time::timeout(RESTORE_START_TIMEOUT, async {
+        loop {
+            match lifecycle::state(server_state).await {
+                ServerLifecycle::Running => return Ok(()),
+                ServerLifecycle::Stopped | ServerLifecycle::Crashed => {
+                    return Err(BackupError::StartFailed);
+                }
+                _ => tokio::time::sleep(Duration::from_secs(2)).await,
+            }
+        }
+    })
+    .await
+    .map_err(|_| BackupError::StartTimeout)?
+}
+
+async fn roll_back(
+    server_state: &ServerState,
+    restore: &lifecycle::Restore<'_>,
+    set_aside: bool,
+    reason: String,
+) -> BackupError {
+    log::error!("Restore failed, putting the previous world back: {reason}");
+    let result = async {
+        if docker::container_status(server_state).await?.running
+            && let Err(e) = restore.stop().await
+        {
+            log::warn!("Could not stop the restored world, killing it: {e}");
+            restore.kill().await?;
+        }
+        run_script(
+            server_state,
+            &roll_back_script(&server_state.bot_config.backup_paths, set_aside),
+        )
+        .await?;
+        restore.start().await?;
+        Ok::<_, BackupError>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => BackupError::RolledBack(reason),
+        Err(e) => {
+            log::error!("Rolling back the restore failed: {e}");
+            BackupError::RollbackFailed {
+                reason,
+                error: e.to_string(),
+            }
+        }
+    }
+}
+
+/// Which backups to delete, keeping the newest one of each of the last `keep_daily` days and
+/// `keep_weekly` weeks that have backups.
+pub fn to_prune(
+    backups: &[(i64, DateTime<Utc>)],
+    keep_daily: usize,
+    keep_weekly: usize,
+) -> Vec<i64> {
+    let mut newest_first = backups.to_vec();
+    newest_first.sort_by_key(|(_, created_at)| std::cmp::Reverse(*created_at));
+
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut prune = Vec::new();
+    for (id, created_at) in newest_first {
+        let mut keep = false;
+        if days.len() < keep_daily && days.insert(created_at.date_naive()) {
+            keep = true;
+        }
+        let week = created_at.iso_week();
+        if weeks.len() < keep_weekly && weeks.insert((week.year(), week.week())) {
+            keep = true;
+        }
+        if !keep {
+            prune.push(id);
+        }
+    }
+    prune
+}
+
+async fn prune(server_state: &ServerState, backup_dir: &Path) -> Result<(), BackupError> {
+    let backups = BackupRecord::get_all(&server_state.db).await?;
+    let ids: Vec<_> = backups
+        .iter()
+        .map(|backup| (backup.id, backup.created_at))
+        .collect();
+    let prune = to_prune(
+        &ids,
+        server_state.bot_config.backup_keep_daily,
+        server_state.bot_config.backup_keep_weekly,
+    );
+
+    let store = ChunkStore::new(backup_dir);
+    for backup in backups.iter().filter(|backup| prune.contains(&backup.id)) {
+        log::info!("Deleting old backup {}", backup.name);
+        store.remove_snapshot(&backup.name).await?;
+        match tokio::fs::remove_dir_all(backup_dir.join(&backup.name)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        BackupRecord::remove(backup.id)
+            .execute(&server_state.db)
+            .await?;
+    }
+
+    // Also picks up chunks left behind by backups that failed halfway
+    let (chunks, bytes) = store.collect_garbage().await?;
+    if chunks > 0 {
+        log::info!("Deleted {chunks} chunks no backup uses anymore, {bytes} bytes");
+    }
+    Ok(())
+}
+
+/// What `/backup verify` found for a backup.
+pub enum BackupHealth {
+    Checked(Verification),
+    /// A directory of tar archives from before the chunk store, which has nothing to check against
+    Legacy,
+    Missing,
+}
+
+/// Checks the chunks of the backups, or of just `id`, against their hashes.
+pub async fn verify(
+    server_state: &ServerState,
+    id: Option<i64>,
+) -> Result<Vec<(BackupRecord, BackupHealth)>, BackupError> {
+    let backup_dir = server_state
+        .bot_config
+        .backup_dir
+        .as_ref()
+        .ok_or(BackupError::Disabled)?;
+    // Pruning while checking would make chunks look missing
+    let _lock = lock(server_state)?;
+    verify_locked(server_state, backup_dir, id).await
+}
+
+async fn verify_locked(
+    server_state: &ServerState,
+    backup_dir: &Path,
+    id: Option<i64>,
+) -> Result<Vec<(BackupRecord, BackupHealth)>, BackupError> {
+    let store = ChunkStore::new(backup_dir);
+    let mut backups = Vec::new();
+    let mut snapshots = Vec::new();
+    for backup in BackupRecord::get_all(&server_state.db).await? {
+        if id.is_some_and(|id| id != backup.id) {
+            continue;
+        }
+        // Snapshots are checked together, so shared chunks are only read once
+        let health = match store.load_snapshot(&backup.name).await? {
+            Some(snapshot) => {
+                snapshots.push(snapshot);
+                None
+            }
+            None if tokio::fs::try_exists(backup_dir.join(&backup.name)).await? => {
+                Some(BackupHealth::Legacy)
+            }
+            None => Some(BackupHealth::Missing),
+        };
+        backups.push((backup, health));
+    }
+
+    let mut verifications = store.verify(&snapshots).await?.into_iter();
+    Ok(backups
+        .into_iter()
+        .map(|(backup, health)| {
+            let health = health
+                .unwrap_or_else(|| BackupHealth::Checked(verifications.next().unwrap_or_default()));
+            (backup, health)
+        })
+        .collect())
+}
+
+/// Backs up on BACKUP_INTERVAL_HOURS for as long as the bot runs.
+pub async fn backup_periodically(server_state: Arc<ServerState>, http: Arc<Http>) {
+    let (Some(interval), Some(_)) = (
+        server_state.bot_config.backup_interval,
+        &server_state.bot_config.backup_dir,
+    ) else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(interval);
+    // The first tick is straight away, and the bot just started
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match create(&server_state, BackupTrigger::Scheduled, None).await {
+            // Failures are alerted on inside
+            Ok(record) => {
+                let _ = offsite::upload_and_alert(&server_state, &http, &record).await;
+            }
+            Err(e) => {
+                let _ = alerts::alert(
+                    &server_state,
+                    &http,
+                    format!(":x: Scheduled backup failed: {e}"),
+                )
+                .await
+                .map_err(|e| log::error!("Error in alert: {e}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // The 5th of october 2026 is a monday
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn archive_names() {
+        assert_eq!(archive_name("/data/world"), "world.tar");
+        assert_eq!(archive_name("/data/world_nether/"), "world_nether.tar");
+        assert_eq!(archive_name("world"), "world.tar");
+    }
+
+    #[test]
+    fn parent_dirs() {
+        assert_eq!(parent_dir("/data/world"), "/data");
+        assert_eq!(parent_dir("/data/world_nether/"), "/data");
+        assert_eq!(parent_dir("/world"), "/");
+        assert_eq!(parent_dir("world"), "/");
+    }
+
+    #[test]
+    fn restore_scripts() {
+        let paths = vec!["/data/world".to_string(), "/data/it's".to_string()];
+        assert_eq!(
+            set_aside_script(&paths),
+            "set -e\n\
+            if [ -e '/data/world.pre-restore' ]; then exit 3; fi\n\
+            if [ -e '/data/it'\\''s.pre-restore' ]; then exit 3; fi\n\
+            if [ -e '/data/world' ]; then mv '/data/world' '/data/world.pre-restore'; fi\n\
+            if [ -e '/data/it'\\''s' ]; then mv '/data/it'\\''s' '/data/it'\\''s.pre-restore'; fi"
+        );
+        assert_eq!(
+            roll_back_script(&paths[..1], false),
+            "set -e\n\
+            if [ -e '/data/world.pre-restore' ]; then rm -rf '/data/world'; mv '/data/world.pre-restore' '/data/world'; fi"
+        );
+        assert_eq!(
+            roll_back_script(&paths[..1], true),
+            "set -e\n\
+            if [ -e '/data/world.pre-restore' ]; then rm -rf '/data/world'; mv '/data/world.pre-restore' '/data/world'; else rm -rf '/data/world'; fi"
+        );
+        assert_eq!(
+            clean_up_script(&paths),
+            "rm -rf '/data/world.pre-restore' '/data/it'\\''s.pre-restore'"
+        );
+    }
+
+    #[test]
+    fn leftovers_stop_the_restore_before_anything_moves() {
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let root = std::env::temp_dir().join(format!("set-aside-test-{nanos}"));
+        let world = root.join("world");
+        let nether = root.join("world_nether");
+        std::fs::create_dir_all(&world).unwrap();
+        std::fs::create_dir_all(&nether).unwrap();
+        // Left behind by an earlier restore, maybe the only copy of an older world
+        std::fs::create_dir_all(root.join("world_nether.pre-restore")).unwrap();
+
+        let paths = [world, nether].map(|path| path.to_string_lossy().into_owned());
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(set_aside_script(&paths))
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(LEFTOVER_EXIT_CODE as i32));
+        // The world before the leftover was not moved either
+        assert!(root.join("world").exists());
+        assert!(!root.join("world.pre-restore").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn roll_back_deletes_directories_that_were_not_there_before() {
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let root = std::env::temp_dir().join(format!("roll-back-test-{nanos}"));
+        let world = root.join("world");
+        let nether = root.join("world_nether");
+        std::fs::create_dir_all(&world).unwrap();
+        std::fs::write(world.join("old"), "").unwrap();
+        let paths = [world, nether].map(|path| path.to_string_lossy().into_owned());
+        let run = |script: String| {
+            let status = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(script)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+
+        run(set_aside_script(&paths));
+        // Extracted by the restore, the nether only exists in the backup
+        std::fs::create_dir_all(root.join("world/new")).unwrap();
+        std::fs::create_dir_all(root.join("world_nether")).unwrap();
+        run(roll_back_script(&paths, true));
+
+        assert!(root.join("world/old").exists());
+        assert!(!root.join("world/new").exists());
+        assert!(!root.join("world_nether").exists());
+        assert!(!root.join("world.pre-restore").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn saved_lines() {
+        assert!(is_saved("[12:00:00] [Server thread/INFO]: Saved the game"));
+        assert!(is_saved("[12:00:00 INFO]: Saved the game"));
+        assert!(!is_saved(
+            "[12:00:00] [Server thread/INFO]: Saving the game (this may take a moment!)"
+        ));
+        assert!(!is_saved(
+            "[12:00:00] [Server thread/INFO]: <sally> Saved the game"
+        ));
+    }
+
+    #[test]
+    fn keeps_newest_per_day_and_week() {
+        let backups = [
+            (1, at(5, 6)),
+            (2, at(5, 18)),
+            (3, at(12, 6)),
+            (4, at(13, 6)),
+            (5, at(14, 6)),
+            (6, at(14, 18)),
+            (7, at(15, 6)),
+        ];
+
+        // Days: 15th, 14th at 18:00, 13th. Weeks: 12th-15th and 5th at 18:00
+        let mut prune = to_prune(&backups, 3, 2);
+        prune.sort();
+        assert_eq!(prune, vec![1, 3, 5]);
+
+        assert_eq!(to_prune(&backups, 10, 10), vec![5, 1]);
+        let mut prune = to_prune(&backups, 0, 0);
+        prune.sort();
+        assert_eq!(prune, vec![1, 2, 3, 4, 5, 6, 7]);
+    }
+}
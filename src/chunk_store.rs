@@ -0,0 +1,621 @@
+//! A content addressed store for backups, so the parts of the world that did not change between
+//! backups are only kept once.
+//!
+//! Archives are cut into chunks wherever a rolling hash of the last 64 bytes matches a pattern,
+//! so changing a file only changes the chunks around the change instead of shifting every chunk
+//! after it. Minecraft rewrites region files a few sectors at a time, so most chunks are shared
+//! with the previous backup.
+//!
+//! Chunks live in `chunks/` named by their SHA-256, and every snapshot is a manifest in
+//! `snapshots/` listing the chunks of its archives in order.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::PathBuf,
+};
+
+use bytes::Bytes;
+use serenity::futures::{Stream, StreamExt, stream};
+use sha2::{Digest, Sha256};
+
+const MIN_CHUNK_SIZE: usize = 16 * 1024;
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// Matches about once every 64 KiB
+const CUT_MASK: u64 = 0xffff << 48;
+
+/// Fixed pseudo random numbers for the rolling hash. Changing them moves every chunk boundary,
+/// which would make the next backup share nothing with the old ones.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < table.len() {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Splits a stream of bytes into content defined chunks.
+pub struct Chunker {
+    buffer: Vec<u8>,
+    hash: u64,
+}
+
+impl Chunker {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::with_capacity(MAX_CHUNK_SIZE),
+            hash: 0,
+        }
+    }
+
+    /// Feeds in more of the archive, returning the chunks it completed.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        for &byte in data {
+            self.buffer.push(byte);
+            // Shifting pushes each byte out of the hash after 64 more
+            self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+            let len = self.buffer.len();
+            if (len >= MIN_CHUNK_SIZE && self.hash & CUT_MASK == 0) || len >= MAX_CHUNK_SIZE {
+                chunks.push(std::mem::replace(
+                    &mut self.buffer,
+                    Vec::with_capacity(MAX_CHUNK_SIZE),
+                ));
+                self.hash = 0;
+            }
+        }
+        chunks
+    }
+
+    /// Whatever is left after the last cut.
+    pub fn finish(self) -> Option<Vec<u8>> {
+        (!self.buffer.is_empty()).then_some(self.buffer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// A tar archive of one world directory, as the chunks it is made of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+    pub chunks: Vec<ChunkRef>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub archives: Vec<Archive>,
+}
+
+impl Snapshot {
+    pub fn archive(&self, name: &str) -> Option<&Archive> {
+        self.archives.iter().find(|archive| archive.name == name)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.archives.iter().map(|archive| archive.size).sum()
+    }
+
+    /// One `archive <name> <size> <sha256>` line per archive, followed by a `<hash> <size>` line
+    /// per chunk. Names come from BACKUP_PATHS, so they are percent encoded to keep spaces out.
+    fn to_manifest(&self) -> String {
+        let mut manifest = String::new();
+        for archive in &self.archives {
+            manifest.push_str(&format!(
+                "archive {} {} {}\n",
+                encode_name(&archive.name),
+                archive.size,
+                archive.sha256
+            ));
+            for chunk in &archive.chunks {
+                manifest.push_str(&format!("{} {}\n", chunk.hash, chunk.size));
+            }
+        }
+        manifest
+    }
+
+    fn from_manifest(manifest: &str) -> Option<Self> {
+        let mut archives: Vec<Archive> = Vec::new();
+        for line in manifest.lines().filter(|line| !line.is_empty()) {
+            let parts: Vec<_> = line.split(' ').collect();
+            match parts.as_slice() {
+                ["archive", name, size, sha256] => archives.push(Archive {
+                    name: decode_name(name)?,
+                    size: size.parse().ok()?,
+                    sha256: sha256.to_string(),
+                    chunks: Vec::new(),
+                }),
+                [hash, size] if is_hash(hash) => archives.last_mut()?.chunks.push(ChunkRef {
+                    hash: hash.to_string(),
+                    size: size.parse().ok()?,
+                }),
+                _ => return None,
+            }
+        }
+        Some(Self { archives })
+    }
+}
+
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::new();
+    for c in name.chars() {
+        match c {
+            '%' | ' ' | '\t' | '\r' | '\n' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn decode_name(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+/// How a snapshot's chunks held up, counting shared chunks once per snapshot.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub chunks: usize,
+    pub bytes: u64,
+    pub missing: usize,
+    pub corrupt: usize,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.missing == 0 && self.corrupt == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkHealth {
+    Ok,
+    Missing,
+    Corrupt,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkStore {
+    root: PathBuf,
+}
+
+impl ChunkStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn chunk_path(&self, hash: &str) -> PathBuf {
+        self.root.join("chunks").join(&hash[..2]).join(hash)
+    }
+
+    fn snapshot_path(&self, name: &str) -> PathBuf {
+        self.root.join("snapshots").join(name)
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves half a file under the real name.
+    async fn write_atomically(path: &PathBuf, data: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let temporary = path.with_extension("tmp");
+        tokio::fs::write(&temporary, data).await?;
+        tokio::fs::rename(&temporary, path).await
+    }
+
+    /// Stores a chunk unless it is already there, returning whether it was new.
+    async fn put_chunk(&self, data: &[u8]) -> io::Result<(ChunkRef, bool)> {
+        let hash = hex::encode(Sha256::digest(data));
+        let path = self.chunk_path(&hash);
+        let new = !tokio::fs::try_exists(&path).await?;
+        if new {
+            Self::write_atomically(&path, data).await?;
+        }
+        Ok((
+            ChunkRef {
+                hash,
+                size: data.len() as u64,
+            },
+            new,
+        ))
+    }
+
+    /// Reads a chunk back, refusing it if it no longer matches its hash.
+    async fn read_chunk(&self, chunk: &ChunkRef) -> io::Result<Bytes> {
+        let data = tokio::fs::read(self.chunk_path(&chunk.hash)).await?;
+        if hex::encode(Sha256::digest(&data)) != chunk.hash {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("chunk {} is corrupt", chunk.hash),
+            ));
+        }
+        Ok(data.into())
+    }
+
+    async fn chunk_health(&self, chunk: &ChunkRef) -> io::Result<ChunkHealth> {
+        match self.read_chunk(chunk).await {
+            Ok(data) if data.len() as u64 == chunk.size => Ok(ChunkHealth::Ok),
+            Ok(_) => Ok(ChunkHealth::Corrupt),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ChunkHealth::Missing),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Ok(ChunkHealth::Corrupt),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn write_archive(&self, name: &str) -> ArchiveWriter {
+        ArchiveWriter {
+            store: self.clone(),
+            chunker: Chunker::new(),
+            hasher: Sha256::new(),
+            archive: Archive {
+                name: name.to_string(),
+                size: 0,
+                sha256: String::new(),
+                chunks: Vec::new(),
+            },
+            new_bytes: 0,
+        }
+    }
+
+    /// Streams an archive back out of its chunks.
+    pub fn read_archive(
+        &self,
+        archive: &Archive,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+        let store = self.clone();
+        stream::iter(archive.chunks.clone()).then(move |chunk| {
+            let store = store.clone();
+            async move { store.read_chunk(&chunk).await }
+        })
+    }
+
+    pub async fn save_snapshot(&self, name: &str, snapshot: &Snapshot) -> io::Result<()> {
+        Self::write_atomically(&self.snapshot_path(name), snapshot.to_manifest().as_bytes()).await
+    }
+
+    pub async fn load_snapshot(&self, name: &str) -> io::Result<Option<Snapshot>> {
+        let manifest = match tokio::fs::read_to_string(self.snapshot_path(name)).await {
+            Ok(manifest) => manifest,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Snapshot::from_manifest(&manifest).map(Some).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the manifest of snapshot {name} is damaged"),
+            )
+        })
+    }
+
+    pub async fn remove_snapshot(&self, name: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.snapshot_path(name)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn snapshot_names(&self) -> io::Result<Vec<String>> {
+        let mut entries = match tokio::fs::read_dir(self.root.join("snapshots")).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.ends_with(".tmp") {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Deletes every chunk no snapshot refers to, returning how many and their size.
+    ///
+    /// Stops at the first manifest it cannot read, since the chunks it lists would look unused.
+    pub async fn collect_garbage(&self) -> io::Result<(usize, u64)> {
+        let mut referenced = HashSet::new();
+        for name in self.snapshot_names().await? {
+            let snapshot = self.load_snapshot(&name).await?.unwrap_or_default();
+            for archive in snapshot.archives {
+                referenced.extend(archive.chunks.into_iter().map(|chunk| chunk.hash));
+            }
+        }
+
+        let mut removed = 0;
+        let mut removed_bytes = 0;
+        let mut prefixes = match tokio::fs::read_dir(self.root.join("chunks")).await {
+            Ok(prefixes) => prefixes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(e),
+        };
+        while let Some(prefix) = prefixes.next_entry().await? {
+            let mut chunks = tokio::fs::read_dir(prefix.path()).await?;
+            while let Some(chunk) = chunks.next_entry().await? {
+                if referenced.contains(chunk.file_name().to_string_lossy().as_ref()) {
+                    continue;
+                }
+                removed_bytes += chunk.metadata().await?.len();
+                tokio::fs::remove_file(chunk.path()).await?;
+                removed += 1;
+            }
+        }
+        Ok((removed, removed_bytes))
+    }
+
+    /// Checks that every chunk of the snapshots is there and still matches its hash.
+    pub async fn verify(&self, snapshots: &[Snapshot]) -> io::Result<Vec<Verification>> {
+        // Snapshots share most of their chunks, each only needs reading once
+        let mut checked = HashMap::new();
+        let mut verifications = Vec::new();
+        for snapshot in snapshots {
+            let mut verification = Verification::default();
+            let mut seen = HashSet::new();
+            for chunk in snapshot.archives.iter().flat_map(|archive| &archive.chunks) {
+                if !seen.insert(&chunk.hash) {
+                    continue;
+                }
+                let health = match checked.get(&chunk.hash) {
+                    Some(health) => *health,
+                    None => {
+                        let health = self.chunk_health(chunk).await?;
+                        checked.insert(chunk.hash.clone(), health);
+                        health
+                    }
+                };
+                verification.chunks += 1;
+                verification.bytes += chunk.size;
+                match health {
+                    ChunkHealth::Ok => {}
+                    ChunkHealth::Missing => verification.missing += 1,
+                    ChunkHealth::Corrupt => verification.corrupt += 1,
+                }
+            }
+            verifications.push(verification);
+        }
+        Ok(verifications)
+    }
+}
+
+/// Chunks an archive into the store as it is streamed in.
+pub struct ArchiveWriter {
+    store: ChunkStore,
+    chunker: Chunker,
+    hasher: Sha256,
+    archive: Archive,
+    new_bytes: u64,
+}
+
+impl ArchiveWriter {
+    async fn put(&mut self, chunk: &[u8]) -> io::Result<()> {
+        let (chunk, new) = self.store.put_chunk(chunk).await?;
+        if new {
+            self.new_bytes += chunk.size;
+        }
+        self.archive.chunks.push(chunk);
+        Ok(())
+    }
+
+    pub async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.hasher.update(data);
+        self.archive.size += data.len() as u64;
+        for chunk in self.chunker.push(data) {
+            self.put(&chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// The finished archive, and how many of its bytes were not in the store yet.
+    pub async fn finish(mut self) -> io::Result<(Archive, u64)> {
+        let chunker = std::mem::replace(&mut self.chunker, Chunker::new());
+        if let Some(chunk) = chunker.finish() {
+            self.put(&chunk).await?;
+        }
+        self.archive.sha256 = hex::encode(self.hasher.finalize_reset());
+        Ok((self.archive, self.new_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    /// Reproducible bytes that do not repeat
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn chunk_all(data: &[u8], write_size: usize) -> Vec<Vec<u8>> {
+        let mut chunker = Chunker::new();
+        let mut chunks: Vec<_> = data
+            .chunks(write_size)
+            .flat_map(|data| chunker.push(data))
+            .collect();
+        chunks.extend(chunker.finish());
+        chunks
+    }
+
+    #[test]
+    fn chunks_within_limits_regardless_of_writes() {
+        let data = noise(3 * 1024 * 1024, 1);
+        let chunks = chunk_all(&data, 1000);
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks.len() > 10);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!((MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk.len()));
+        }
+        assert_eq!(chunk_all(&data, 65536), chunks);
+    }
+
+    #[test]
+    fn inserting_only_changes_nearby_chunks() {
+        let data = noise(2 * 1024 * 1024, 2);
+        let mut edited = data.clone();
+        edited.splice(100_000..100_000, noise(777, 3));
+
+        let before: HashSet<_> = chunk_all(&data, 4096).into_iter().collect();
+        let after = chunk_all(&edited, 4096);
+        let changed = after
+            .iter()
+            .filter(|chunk| !before.contains(*chunk))
+            .count();
+        assert!(changed <= 2, "{changed} of {} chunks changed", after.len());
+    }
+
+    #[test]
+    fn manifests() {
+        let hash = |c: char| c.to_string().repeat(64);
+        let snapshot = Snapshot {
+            archives: vec![
+                Archive {
+                    name: "world.tar".to_string(),
+                    size: 30,
+                    sha256: hash('a'),
+                    chunks: vec![
+                        ChunkRef {
+                            hash: hash('b'),
+                            size: 10,
+                        },
+                        ChunkRef {
+                            hash: hash('c'),
+                            size: 20,
+                        },
+                    ],
+                },
+                Archive {
+                    name: "world_nether.tar".to_string(),
+                    size: 0,
+                    sha256: hash('d'),
+                    chunks: vec![],
+                },
+            ],
+        };
+        let manifest = snapshot.to_manifest();
+        assert_eq!(Snapshot::from_manifest(&manifest), Some(snapshot));
+        assert_eq!(
+            Snapshot::from_manifest(&format!("{} 10\n", hash('b'))),
+            None
+        );
+        assert_eq!(Snapshot::from_manifest("archive world.tar ten abc\n"), None);
+    }
+
+    #[test]
+    fn manifests_with_spaces_in_names() {
+        let snapshot = Snapshot {
+            archives: vec![Archive {
+                name: "My World 100%.tar".to_string(),
+                size: 0,
+                sha256: "a".repeat(64),
+                chunks: vec![],
+            }],
+        };
+        let manifest = snapshot.to_manifest();
+        assert!(manifest.starts_with("archive My%20World%20100%25.tar 0 "));
+        assert_eq!(Snapshot::from_manifest(&manifest), Some(snapshot));
+
+        // Cut off escapes are refused rather than guessed at
+        assert_eq!(
+            Snapshot::from_manifest(&format!("archive world%2 0 {}\n", "a".repeat(64))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn deduplicates_collects_garbage_and_verifies() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("chunk-store-test-{nanos}"));
+        let store = ChunkStore::new(&root);
+
+        let data = noise(1024 * 1024, 4);
+        let mut writer = store.write_archive("world.tar");
+        writer.write(&data).await.unwrap();
+        let (first, new_bytes) = writer.finish().await.unwrap();
+        assert_eq!(new_bytes, data.len() as u64);
+        assert_eq!(first.sha256, hex::encode(Sha256::digest(&data)));
+
+        let mut edited = data.clone();
+        edited[500_000] ^= 0xff;
+        let mut writer = store.write_archive("world.tar");
+        writer.write(&edited).await.unwrap();
+        let (second, new_bytes) = writer.finish().await.unwrap();
+        assert!(new_bytes <= MAX_CHUNK_SIZE as u64);
+
+        let first = Snapshot {
+            archives: vec![first],
+        };
+        let second = Snapshot {
+            archives: vec![second],
+        };
+        store.save_snapshot("first", &first).await.unwrap();
+        store.save_snapshot("second", &second).await.unwrap();
+        let read: Vec<_> = store
+            .read_archive(&second.archives[0])
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(read.concat(), edited);
+
+        store.remove_snapshot("first").await.unwrap();
+        let (removed, removed_bytes) = store.collect_garbage().await.unwrap();
+        assert!(removed >= 1);
+        assert!(removed_bytes <= MAX_CHUNK_SIZE as u64);
+        let verification = &store.verify(std::slice::from_ref(&second)).await.unwrap()[0];
+        assert!(verification.is_ok());
+        assert_eq!(verification.bytes, edited.len() as u64);
+
+        let chunks = &second.archives[0].chunks;
+        tokio::fs::write(store.chunk_path(&chunks[0].hash), b"rot")
+            .await
+            .unwrap();
+        tokio::fs::remove_file(store.chunk_path(&chunks[1].hash))
+            .await
+            .unwrap();
+        let verification = &store.verify(std::slice::from_ref(&second)).await.unwrap()[0];
+        assert_eq!((verification.missing, verification.corrupt), (1, 1));
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::Utc;
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, GuildId, Permissions, ResolvedOption,
+    ResolvedValue, UserId,
+};
+
+use crate::{server_state::ServerState, sql::audit::AuditEntry};
+
+use super::{CommandError, CommandResult, Context};
+
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 25;
+/// Keeps each entry to about a line
+const MAX_FIELD_LENGTH: usize = 30;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Commands that change the server or the bot, which get an audit log entry.
+pub fn is_audited(command_name: &str) -> bool {
+    matches!(
+        command_name,
+        "restart"
+            | "start"
+            | "stop"
+            | "kill"
+            | "console"
+            | "whitelist"
+            | "subscribe"
+            | "unsubscribe"
+            | "snitch_add"
+            | "snitch_remove"
+            | "permissions"
+            | "schedule"
+            | "backup"
+    )
+}
+
+/// Writes the options the way they would be typed, like `grant role:@Mods capability:restart`.
+fn format_options(options: &[ResolvedOption]) -> String {
+    options
+        .iter()
+        .map(|option| match &option.value {
+            ResolvedValue::SubCommand(options) | ResolvedValue::SubCommandGroup(options) => {
+                match format_options(options).as_str() {
+                    "" => option.name.to_string(),
+                    options => format!("{} {options}", option.name),
+                }
+            }
+            ResolvedValue::Boolean(b) => format!("{}:{b}", option.name),
+            ResolvedValue::Integer(i) => format!("{}:{i}", option.name),
+            ResolvedValue::Number(n) => format!("{}:{n}", option.name),
+            ResolvedValue::String(s) => format!("{}:{s}", option.name),
+            ResolvedValue::Role(role) => format!("{}:@{}", option.name, role.name),
+            ResolvedValue::User(user, _) => format!("{}:@{}", option.name, user.name),
+            ResolvedValue::Channel(channel) => format!("{}:<#{}>", option.name, channel.id),
+            value => format!("{}:{value:?}", option.name),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Records that the user ran the command, and how it went.
+pub async fn record(ctx: &Context, result: &str) -> CommandResult {
+    let server_state = ctx.get_server_state().await;
+    write(
+        &server_state,
+        ctx.command.guild_id,
+        ctx.command.user.id,
+        &ctx.command.user.name,
+        &ctx.command.data.name,
+        format_options(&ctx.command.data.options()),
+        result,
+    )
+    .await
+}
+
+/// Records an action that did not come straight from a slash command, like a button press.
+pub async fn write(
+    server_state: &ServerState,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    user_name: &str,
+    command: &str,
+    options: String,
+    result: &str,
+) -> CommandResult {
+    AuditEntry {
+        created_at: Utc::now(),
+        guild_id: guild_id.map(|guild_id| guild_id.get().into()),
+        user_id: user_id.get().into(),
+        user_name: user_name.to_string(),
+        command: command.to_string(),
+        options,
+        result: result.to_string(),
+    }
+    .insert()
+    .execute(&server_state.db)
+    .await?;
+
+    Ok(())
+}
+
+fn truncate(s: &str) -> String {
+    match s.char_indices().nth(MAX_FIELD_LENGTH) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+fn format_entry(entry: &AuditEntry) -> String {
+    let command = match entry.options.as_str() {
+        "" => format!("/{}", entry.command),
+        options => format!("/{} {}", entry.command, truncate(options)),
+    };
+    format!(
+        "<t:{}:f> <@{}> `{}`: {}",
+        entry.created_at.timestamp(),
+        entry.user_id.get(),
+        command.replace('`', "'"),
+        truncate(&entry.result)
+    )
+}
+
+/// The newest entries that fit in one message, saying how many older ones were left out.
+fn format_entries(entries: &[AuditEntry]) -> String {
+    let mut msg = ":scroll: Audit log".to_string();
+    for (shown, entry) in entries.iter().enumerate() {
+        let line = format_entry(entry);
+        let omitted = format!("\n…and {} older entries", entries.len() - shown);
+        // Whatever is added has to leave room to say the rest did not fit
+        let room = if shown + 1 == entries.len() {
+            0
+        } else {
+            omitted.chars().count()
+        };
+        if msg.chars().count() + 1 + line.chars().count() + room > MAX_MESSAGE_LENGTH {
+            msg.push_str(&omitted);
+            break;
+        }
+        msg.push('\n');
+        msg.push_str(&line);
+    }
+    msg
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+    let mut user_id = None;
+    let mut limit = DEFAULT_LIMIT;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("user", ResolvedValue::User(user, _)) => user_id = Some(user.id),
+            ("limit", ResolvedValue::Integer(i)) => limit = i.clamp(1, MAX_LIMIT),
+            _ => return Err(CommandError::BadOptionPassed),
+        }
+    }
+
+    let entries =
+        AuditEntry::get_recent(&ctx.get_server_state().await.db, guild_id, user_id, limit).await?;
+    if entries.is_empty() {
+        ctx.say_ephemeral(":scroll: Nothing has been recorded yet")
+            .await?;
+        return Ok(());
+    }
+
+    ctx.say_ephemeral(format_entries(&entries)).await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("audit")
+        .description("Shows who recently changed the server or the bot")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "Only show this user")
+                .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "limit",
+                "Number of entries to show (default: 10)",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_LIMIT as u64)
+            .required(false),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::sql::audit::AuditEntry;
+
+    use super::{MAX_LIMIT, MAX_MESSAGE_LENGTH, format_entries, is_audited, truncate};
+
+    #[test]
+    fn audited_commands() {
+        for command in [
+            "restart",
+            "stop",
+            "kill",
+            "subscribe",
+            "snitch_add",
+            "permissions",
+        ] {
+            assert!(is_audited(command), "{command}");
+        }
+        for command in ["status", "playtime", "log", "audit"] {
+            assert!(!is_audited(command), "{command}");
+        }
+    }
+
+    #[test]
+    fn truncates_long_fields() {
+        assert_eq!(truncate("ok"), "ok");
+        assert_eq!(
+            truncate("Database error: error returned from database: relation does not exist"),
+            "Database error: error returned…"
+        );
+    }
+
+    #[test]
+    fn entries_fit_in_a_message() {
+        // As long as every field gets
+        let entry = || AuditEntry {
+            created_at: Utc::now(),
+            guild_id: Some(u64::MAX.into()),
+            user_id: u64::MAX.into(),
+            user_name: "sally".to_string(),
+            command: "x".repeat(32),
+            options: "`".repeat(200),
+            result: "e".repeat(200),
+        };
+        let entries: Vec<_> = (0..MAX_LIMIT).map(|_| entry()).collect();
+
+        let msg = format_entries(&entries);
+        assert!(msg.chars().count() <= MAX_MESSAGE_LENGTH);
+        assert!(msg.starts_with(":scroll: Audit log\n<t:"));
+        assert!(msg.ends_with("older entries"));
+
+        // Everything is shown when it fits
+        let msg = format_entries(&entries[..2]);
+        assert_eq!(msg.lines().count(), 3);
+    }
+}
@@ -0,0 +1,327 @@
+use serenity::all::{
+    ButtonStyle, CommandOptionType, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse, ResolvedValue,
+};
+
+use crate::{
+    backup::{self, BackupHealth, BackupTrigger},
+    offsite,
+    server_state::ContextExt,
+    sql::backups::BackupRecord,
+};
+
+use super::{CommandError, CommandResult, Context, audit, stats::format_bytes};
+
+pub const RESTORE_CONFIRM_ID: &str = "backup_restore_confirm";
+pub const RESTORE_CANCEL_ID: &str = "backup_restore_cancel";
+/// Keeps the list under Discord's 2000 characters
+const MAX_LISTED: usize = 20;
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let options = ctx.command.data.options();
+    let subcommand = options.first().ok_or(CommandError::BadOptionIndex(0))?;
+    let server_state = ctx.get_server_state().await;
+
+    match (subcommand.name, &subcommand.value) {
+        ("now", ResolvedValue::SubCommand(_)) => {
+            ctx.say(":floppy_disk: Backing up the world..").await?;
+            let result = backup::create(
+                &server_state,
+                BackupTrigger::Manual,
+                Some(ctx.command.user.id),
+            )
+            .await;
+            let msg = match &result {
+                Ok(backup) => {
+                    let done = format!(
+                        ":white_check_mark: Backup `#{}` done ({}, {} new)",
+                        backup.id,
+                        format_bytes(backup.size_bytes as u64),
+                        format_bytes(backup.stored_bytes as u64)
+                    );
+                    if offsite::is_enabled(&server_state) {
+                        ctx.update_msg(format!("{done}, uploading it offsite.."))
+                            .await?;
+                        match offsite::upload_and_alert(&server_state, &ctx.context.http, backup)
+                            .await
+                        {
+                            Ok(()) => format!("{done} and uploaded offsite"),
+                            Err(e) => format!("{done}, but uploading it offsite failed:\n{e}"),
+                        }
+                    } else {
+                        done
+                    }
+                }
+                Err(e) => format!("Failed to back up:\n{e}"),
+            };
+            ctx.update_msg(msg).await?;
+            result.map_err(|e| CommandError::Operation(e.to_string()))?;
+        }
+        ("list", ResolvedValue::SubCommand(_)) => {
+            let backups = BackupRecord::get_all(&server_state.db).await?;
+            if backups.is_empty() {
+                ctx.say("There are no backups yet").await?;
+                return Ok(());
+            }
+
+            let mut lines: Vec<_> = backups
+                .iter()
+                .take(MAX_LISTED)
+                .map(|backup| {
+                    let by = match &backup.created_by {
+                        Some(user_id) => format!(" by <@{}>", user_id.get()),
+                        None => String::new(),
+                    };
+                    format!(
+                        "`#{}` <t:{}:f> {} ({} new), {}{by}",
+                        backup.id,
+                        backup.created_at.timestamp(),
+                        format_bytes(backup.size_bytes as u64),
+                        format_bytes(backup.stored_bytes as u64),
+                        backup.trigger
+                    )
+                })
+                .collect();
+            if backups.len() > MAX_LISTED {
+                lines.push(format!("..and {} older", backups.len() - MAX_LISTED));
+            }
+            ctx.say(lines.join("\n")).await?;
+        }
+        ("verify", ResolvedValue::SubCommand(options)) => {
+            let id = match options.first().map(|option| &option.value) {
+                Some(ResolvedValue::Integer(id)) => Some(*id),
+                _ => None,
+            };
+            ctx.say(":mag: Checking backups..").await?;
+            let msg = match backup::verify(&server_state, id).await {
+                Ok(backups) if backups.is_empty() => match id {
+                    Some(id) => format!("There is no backup `#{id}`"),
+                    None => "There are no backups yet".to_string(),
+                },
+                Ok(backups) => {
+                    let damaged = backups
+                        .iter()
+                        .filter(
+                            |(_, health)| !matches!(health, BackupHealth::Checked(v) if v.is_ok()),
+                        )
+                        .count();
+                    let mut lines: Vec<_> = backups
+                        .iter()
+                        .take(MAX_LISTED)
+                        .map(|(backup, health)| format!("`#{}` {}", backup.id, describe(health)))
+                        .collect();
+                    if backups.len() > MAX_LISTED {
+                        lines.push(format!("..and {} older", backups.len() - MAX_LISTED));
+                    }
+                    let summary = match damaged {
+                        0 => ":white_check_mark: Every backup checked out".to_string(),
+                        n => format!(":x: {n} of {} backups have problems", backups.len()),
+                    };
+                    format!("{summary}\n{}", lines.join("\n"))
+                }
+                Err(e) => format!("Failed to check the backups:\n{e}"),
+            };
+            ctx.update_msg(msg).await?;
+        }
+        ("restore", ResolvedValue::SubCommand(options)) => {
+            let id = match options.first().map(|option| &option.value) {
+                Some(ResolvedValue::Integer(id)) => *id,
+                _ => return Err(CommandError::BadOptionPassed),
+            };
+            let is_admin = ctx
+                .command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.administrator());
+            if !is_admin {
+                ctx.say_ephemeral("Only administrators can restore backups")
+                    .await?;
+                return Ok(());
+            }
+            let Some(backup) = BackupRecord::get(&server_state.db, id).await? else {
+                ctx.say_ephemeral(format!("There is no backup `#{id}`"))
+                    .await?;
+                return Ok(());
+            };
+
+            let online = server_state.mutables.read().await.online_players.len();
+            let user_id = ctx.command.user.id;
+            ctx.command
+                .create_response(
+                    &ctx.context.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!(
+                                ":warning: Replace the world with backup `#{id}` from <t:{}:f>? The server will be stopped, {online} players are online",
+                                backup.created_at.timestamp()
+                            ))
+                            .components(vec![CreateActionRow::Buttons(vec![
+                                CreateButton::new(format!("{RESTORE_CONFIRM_ID}:{user_id}:{id}"))
+                                    .label("Restore")
+                                    .style(ButtonStyle::Danger),
+                                CreateButton::new(format!("{RESTORE_CANCEL_ID}:{user_id}"))
+                                    .label("Cancel")
+                                    .style(ButtonStyle::Secondary),
+                            ])]),
+                    ),
+                )
+                .await?;
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
+
+    Ok(())
+}
+
+fn describe(health: &BackupHealth) -> String {
+    match health {
+        BackupHealth::Checked(verification) if verification.is_ok() => format!(
+            "ok, {} chunks ({})",
+            verification.chunks,
+            format_bytes(verification.bytes)
+        ),
+        BackupHealth::Checked(verification) => format!(
+            ":x: {} of {} chunks missing and {} corrupt",
+            verification.missing, verification.chunks, verification.corrupt
+        ),
+        BackupHealth::Legacy => {
+            "is a tar backup from before the chunk store, not checked".to_string()
+        }
+        BackupHealth::Missing => ":x: its files are missing".to_string(),
+    }
+}
+
+/// Handles the buttons under a restore confirmation, which only the user who asked may press.
+pub async fn handle_component(
+    ctx: &serenity::all::Context,
+    component: &ComponentInteraction,
+) -> CommandResult {
+    let mut parts = component.data.custom_id.split(':');
+    let (Some(action), Some(user_id)) = (parts.next(), parts.next()) else {
+        return Err(CommandError::BadOptionPassed);
+    };
+    if user_id != component.user.id.to_string() {
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Only the person who ran /backup restore can answer this")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let update = |content: &str| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(vec![]),
+        )
+    };
+    match action {
+        RESTORE_CANCEL_ID => {
+            component
+                .create_response(&ctx.http, update("Restore cancelled"))
+                .await?;
+        }
+        RESTORE_CONFIRM_ID => {
+            let id: i64 = parts
+                .next()
+                .and_then(|id| id.parse().ok())
+                .ok_or(CommandError::BadOptionPassed)?;
+            component
+                .create_response(
+                    &ctx.http,
+                    update(&format!(":rewind: Restoring backup `#{id}`..")),
+                )
+                .await?;
+
+            let server_state = ctx.get_server_state().await;
+            let result = match BackupRecord::get(&server_state.db, id).await? {
+                Some(backup) => backup::restore(&server_state, &backup)
+                    .await
+                    .map_err(|e| e.to_string()),
+                None => Err(format!("There is no backup `#{id}` anymore")),
+            };
+            let outcome = match &result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => e.clone(),
+            };
+            if let Err(e) = audit::write(
+                &server_state,
+                component.guild_id,
+                component.user.id,
+                &component.user.name,
+                "backup",
+                format!("restore id:{id}"),
+                &outcome,
+            )
+            .await
+            {
+                log::error!("Cannot write audit log: {e}");
+            }
+
+            let msg = match result {
+                Ok(()) => format!(":white_check_mark: Restored backup `#{id}`, the server is up"),
+                Err(e) => format!("Failed to restore:\n{e}"),
+            };
+            component
+                .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+                .await?;
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("backup")
+        .description("Back up the world")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "now",
+            "Back up the world now",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the backups",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "verify",
+                "Check the backups are intact",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "id",
+                    "Only check this backup",
+                )
+                .min_int_value(1),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "restore",
+                "Replace the world with a backup. Administrators only",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "id",
+                    "The backup number from /backup list",
+                )
+                .min_int_value(1)
+                .required(true),
+            ),
+        )
+}
@@ -0,0 +1,117 @@
+use serenity::{
+    all::{CommandOptionType, CreateCommand, CreateCommandOption, ResolvedValue},
+    utils::MessageBuilder,
+};
+
+use super::{CommandError, CommandResult, Context};
+
+/// Commands whose output can show player IP addresses, only shown to the user who ran them
+const SENSITIVE_COMMANDS: [&str; 4] = ["banlist", "ban-ip", "pardon-ip", "whitelist"];
+/// Leaves room for the code block and the note about cut lines
+const MAX_OUTPUT_LENGTH: usize = 1900;
+
+/// Puts the output in a code block, cutting lines off the end if it is too long for a message.
+fn format_output(output: &str) -> String {
+    let output = output.trim();
+    if output.is_empty() {
+        return "The server did not reply".to_string();
+    }
+
+    let lines: Vec<_> = output.lines().collect();
+    let mut shown = lines.len();
+    while shown > 0 && lines[..shown].join("\n").chars().count() > MAX_OUTPUT_LENGTH {
+        shown -= 1;
+    }
+
+    let mut msg = MessageBuilder::new();
+    msg.push_codeblock_safe(lines[..shown].join("\n"), None);
+    if shown < lines.len() {
+        msg.push(format!("{} more lines not shown", lines.len() - shown));
+    }
+    msg.build()
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let mut command = None;
+    let mut private = false;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("command", ResolvedValue::String(s)) => command = Some(s.trim()),
+            ("private", ResolvedValue::Boolean(b)) => private = b,
+            _ => return Err(CommandError::BadOptionPassed),
+        }
+    }
+    let command = command.ok_or(CommandError::BadOptionIndex(0))?;
+    let command = command.strip_prefix('/').unwrap_or(command);
+
+    let server_state = ctx.get_server_state().await;
+    if let Err(e) = crate::console::check_permitted(&server_state.bot_config, command) {
+        ctx.say_ephemeral(format!(":no_entry: {e}")).await?;
+        return Err(CommandError::Operation(e.to_string()));
+    }
+
+    let running = MessageBuilder::new()
+        .push("Running ")
+        .push_mono_safe(command)
+        .build();
+    let sensitive = crate::console::command_names(command)
+        .iter()
+        .any(|name| SENSITIVE_COMMANDS.contains(&name.as_str()));
+    if private || sensitive {
+        ctx.say_ephemeral(running).await?;
+    } else {
+        ctx.say(running).await?;
+    }
+
+    let result = crate::console::run_command(&server_state, command).await;
+    let msg = match &result {
+        Ok(output) => format_output(output),
+        Err(e) => format!("Failed to run command:\n{e}"),
+    };
+    ctx.update_msg(msg).await?;
+
+    result
+        .map(|_| ())
+        .map_err(|e| CommandError::Operation(e.to_string()))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("console")
+        .description("Runs a command on the server console")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "command",
+                "Command to run, like `list`",
+            )
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "private",
+            "Only show the output to you",
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_cut_to_fit() {
+        assert_eq!(format_output("  \n"), "The server did not reply");
+        assert_eq!(
+            format_output("There are 0 of a max of 20 players online: "),
+            "```\nThere are 0 of a max of 20 players online:\n```"
+        );
+
+        let output: Vec<_> = (0..100)
+            .map(|i| format!("{i:03} {}", "x".repeat(50)))
+            .collect();
+        let msg = format_output(&output.join("\n"));
+        assert!(msg.chars().count() <= 2000);
+        assert!(msg.contains("000 "));
+        assert!(!msg.contains("099 "));
+        assert!(msg.ends_with("more lines not shown"));
+    }
+}
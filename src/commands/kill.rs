@@ -0,0 +1,25 @@
+use serenity::all::CreateCommand;
+
+use crate::lifecycle;
+
+use super::{CommandError, CommandResult, Context};
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    ctx.say(":skull: Killing Server..").await?;
+
+    let server_state = ctx.get_server_state().await;
+    let result = lifecycle::kill(&server_state).await;
+    let msg = if let Err(e) = &result {
+        format!("Failed to kill:\n{e}")
+    } else {
+        ":white_check_mark: Server killed, anything since the last save is lost".to_string()
+    };
+    ctx.update_msg(msg).await?;
+
+    result.map_err(|e| CommandError::Operation(e.to_string()))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("kill")
+        .description("Kills the minecraft server without saving, use /stop if you can")
+}
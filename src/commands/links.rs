@@ -0,0 +1,92 @@
+use std::time::SystemTime;
+
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption, ResolvedValue};
+
+use crate::{
+    active_features::links::{self, CODE_LIFETIME},
+    log_parser,
+    sql::links::AccountLink,
+};
+
+use super::{CommandError, CommandResult, Context};
+
+pub mod link {
+    use super::*;
+
+    pub async fn run(ctx: &Context) -> CommandResult {
+        let player_name = match ctx
+            .command
+            .data
+            .options()
+            .first()
+            .map(|option| &option.value)
+        {
+            Some(ResolvedValue::String(s)) => s.trim(),
+            _ => return Err(CommandError::BadOptionIndex(0)),
+        };
+        if !log_parser::is_valid_player_name(player_name) {
+            ctx.say_ephemeral(format!(":x: `{player_name}` is not a Minecraft name"))
+                .await?;
+            return Ok(());
+        }
+
+        let code = links::new_code();
+        ctx.get_server_state()
+            .await
+            .mutables
+            .write()
+            .await
+            .pending_links
+            .issue(
+                ctx.command.user.id,
+                player_name,
+                code.clone(),
+                SystemTime::now(),
+            );
+
+        ctx.say_ephemeral(format!(
+            ":link: Join the server as {player_name} and type `{code}` in chat within {} minutes to link your account",
+            CODE_LIFETIME.as_secs() / 60
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn register() -> CreateCommand {
+        CreateCommand::new("link")
+            .description("Link your Discord account to your Minecraft account")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "minecraft_name",
+                    "Minecraft player name",
+                )
+                .required(true),
+            )
+    }
+}
+
+pub mod unlink {
+    use super::*;
+
+    pub async fn run(ctx: &Context) -> CommandResult {
+        let sql_res = AccountLink::remove(ctx.command.user.id)
+            .execute(&ctx.get_server_state().await.db)
+            .await?;
+
+        if sql_res.rows_affected() == 1 {
+            ctx.say_ephemeral("Your Minecraft account is no longer linked")
+                .await?;
+        } else {
+            ctx.say_ephemeral("You have not linked a Minecraft account")
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn register() -> CreateCommand {
+        CreateCommand::new("unlink").description("Unlink your Minecraft account")
+    }
+}
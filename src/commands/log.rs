@@ -1,25 +1,284 @@
-use serenity::all::CreateCommand;
+use std::time::Duration;
 
-use crate::docker::get_logs;
+use chrono::Utc;
+use regex::{Regex, RegexBuilder};
+use serenity::{
+    all::{
+        CommandOptionType, CreateAttachment, CreateCommand, CreateCommandOption,
+        CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedValue,
+    },
+    utils::MessageBuilder,
+};
 
-use super::{CommandResult, Context};
+use crate::{
+    docker::get_logs,
+    log_parser::{self, LogLevel},
+};
+
+use super::{CommandError, CommandResult, Context};
+
+const DEFAULT_LINES: usize = 20;
+const MAX_LINES: usize = 2000;
+/// How far back filters look for matching lines
+const MAX_SCANNED_LINES: usize = 10_000;
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Reads durations like `90s`, `30m`, `2h` or `1d12h`.
+fn parse_since(s: &str) -> Option<Duration> {
+    let mut total = 0;
+    let mut number = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return None,
+        };
+        total += number.parse::<u64>().ok()?.checked_mul(unit)?;
+        number.clear();
+    }
+    // A number without a unit is ambiguous
+    (number.is_empty() && total > 0).then(|| Duration::from_secs(total))
+}
+
+/// Searches case insensitively, and for the text as it is if it is not a valid regex.
+fn pattern(contains: &str) -> Option<Regex> {
+    RegexBuilder::new(contains)
+        .case_insensitive(true)
+        .build()
+        .or_else(|_| {
+            RegexBuilder::new(&regex::escape(contains))
+                .case_insensitive(true)
+                .build()
+        })
+        .ok()
+}
+
+/// The lines at `level` or worse that match `pattern`, keeping the last `lines` of them.
+///
+/// Lines without a header, like stack traces, count as the level of the line before them.
+fn filter_lines<'a>(
+    logs: &'a [String],
+    level: Option<LogLevel>,
+    pattern: Option<&Regex>,
+    lines: usize,
+) -> Vec<&'a str> {
+    let mut current_level = None;
+    let matching: Vec<_> = logs
+        .iter()
+        .flat_map(|log| log.lines())
+        .map(|line| line.trim_end())
+        .filter(|line| {
+            if let Some(line_level) = log_parser::parse(line).level {
+                current_level = Some(line_level);
+            }
+            level.is_none_or(|level| current_level.is_some_and(|current| current >= level))
+                && pattern.is_none_or(|pattern| pattern.is_match(line))
+        })
+        .collect();
+    let skip = matching.len().saturating_sub(lines);
+    matching[skip..].to_vec()
+}
 
 pub async fn run(ctx: &Context) -> CommandResult {
-    let (logs, log_errors) = get_logs(ctx.get_server_state().await.as_ref()).await;
+    let mut lines = DEFAULT_LINES;
+    let mut level = None;
+    let mut contains = None;
+    let mut since = None;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("lines", ResolvedValue::Integer(i)) => lines = i.clamp(1, MAX_LINES as i64) as usize,
+            ("level", ResolvedValue::String(s)) => {
+                level = Some(s.parse().map_err(|_| CommandError::BadOptionPassed)?)
+            }
+            ("contains", ResolvedValue::String(s)) => contains = Some(s),
+            ("since", ResolvedValue::String(s)) => match parse_since(s) {
+                Some(duration) => since = Some(duration),
+                None => {
+                    ctx.say_ephemeral(format!(
+                        ":x: `{s}` is not a duration, use something like `30m`, `2h` or `1d`"
+                    ))
+                    .await?;
+                    return Ok(());
+                }
+            },
+            _ => return Err(CommandError::BadOptionPassed),
+        }
+    }
+    let pattern = contains.and_then(pattern);
+
+    let filtered = level.is_some() || pattern.is_some();
+    let since = since.map(|since| Utc::now() - since);
+    let (logs, log_errors) = get_logs(
+        ctx.get_server_state().await.as_ref(),
+        if filtered { MAX_SCANNED_LINES } else { lines },
+        since,
+    )
+    .await;
+    let logs = logs
+        .iter()
+        .map(strip_ansi_escapes::strip_str)
+        .collect::<Vec<_>>();
+    let matching = filter_lines(&logs, level, pattern.as_ref(), lines);
 
-    let mut response = format!(
-        ":scroll: Retrieved server logs...\n```{}```",
-        logs.join("\n")
-    );
+    let mut header = match matching.len() {
+        0 => ":scroll: No log lines matched".to_string(),
+        n => format!(":scroll: Retrieved {n} lines of server logs..."),
+    };
     if !log_errors.is_empty() {
-        response.push_str("\n\n:x: Some errors were generated while retrieving logs.");
+        header.push_str("\n:x: Some errors were generated while retrieving logs.");
+    }
+    if matching.is_empty() {
+        ctx.say(header).await?;
+        return Ok(());
+    }
+
+    let text = matching.join("\n");
+    let inline = MessageBuilder::new()
+        .push_line(&header)
+        .push_codeblock_safe(&text, None)
+        .build();
+    if inline.chars().count() <= MAX_MESSAGE_LENGTH {
+        ctx.say(inline).await?;
+        return Ok(());
     }
 
-    ctx.say(response).await?;
+    // Too long for a message, so it goes in a file instead
+    ctx.command
+        .create_response(
+            &ctx.context.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(header)
+                    .add_file(CreateAttachment::bytes(text, "server.log")),
+            ),
+        )
+        .await?;
 
     Ok(())
 }
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("log").description("Retrieve the log of the server")
+    CreateCommand::new("log")
+        .description("Retrieve the log of the server")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "lines",
+                "How many lines to show, 20 by default",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_LINES as u64),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "level",
+                "Only show lines at this level or worse",
+            )
+            .add_string_choice("INFO", "info")
+            .add_string_choice("WARN", "warn")
+            .add_string_choice("ERROR", "error"),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "contains",
+            "Only show lines containing this text or matching this regex",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "since",
+            "Only show lines from the last while, like 30m, 2h or 1d",
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        // Seconds
+        assert_eq!(parse_since("90s"), Some(Duration::from_secs(90)));
+
+        // Minutes
+        assert_eq!(parse_since("30m"), Some(Duration::from_secs(30 * 60)));
+
+        // Units are case insensitive
+        assert_eq!(parse_since("2H"), Some(Duration::from_secs(2 * 60 * 60)));
+
+        // Several units add up
+        assert_eq!(
+            parse_since("1d12h"),
+            Some(Duration::from_secs(36 * 60 * 60))
+        );
+
+        // Surrounding spaces
+        assert_eq!(parse_since(" 5m "), Some(Duration::from_secs(5 * 60)));
+
+        // Missing unit
+        assert_eq!(parse_since("5"), None);
+
+        // Missing number
+        assert_eq!(parse_since("m"), None);
+
+        // Zero
+        assert_eq!(parse_since("0m"), None);
+
+        // Unknown unit
+        assert_eq!(parse_since("1w"), None);
+
+        // Empty
+        assert_eq!(parse_since(""), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let regex = pattern("sally (joined|left)").unwrap();
+        assert!(regex.is_match("Sally joined the game"));
+        assert!(!regex.is_match("sally (joined|left)"));
+
+        // Not a valid regex, so it is searched for as text
+        let text = pattern("[Server").unwrap();
+        assert!(text.is_match("[12:00:00] [Server thread/INFO]: Done"));
+    }
+
+    #[test]
+    fn filters_lines() {
+        let logs = [
+            "[12:00:00] [Server thread/INFO]: sally joined the game",
+            "[12:00:01] [Server thread/WARN]: Can't keep up!",
+            "[12:00:02] [Server thread/ERROR]: Encountered an unexpected exception\n\
+            java.lang.NullPointerException\n\tat net.minecraft.server.Main",
+            "[12:00:03] [Server thread/INFO]: bob joined the game",
+        ]
+        .map(str::to_string);
+
+        assert_eq!(filter_lines(&logs, None, None, 2).len(), 2);
+        assert_eq!(
+            filter_lines(&logs, Some(LogLevel::Warn), None, 20),
+            vec![
+                "[12:00:01] [Server thread/WARN]: Can't keep up!",
+                "[12:00:02] [Server thread/ERROR]: Encountered an unexpected exception",
+                "java.lang.NullPointerException",
+                "\tat net.minecraft.server.Main",
+            ]
+        );
+        assert_eq!(
+            filter_lines(&logs, Some(LogLevel::Error), None, 1),
+            vec!["\tat net.minecraft.server.Main"]
+        );
+        assert_eq!(
+            filter_lines(&logs, None, pattern("joined").as_ref(), 20),
+            vec![
+                "[12:00:00] [Server thread/INFO]: sally joined the game",
+                "[12:00:03] [Server thread/INFO]: bob joined the game",
+            ]
+        );
+    }
 }
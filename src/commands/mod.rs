@@ -1,18 +1,32 @@
 use std::sync::Arc;
 
+use chrono::TimeDelta;
 use serenity::all::{
     CommandInteraction, CreateInteractionResponse, CreateInteractionResponseMessage,
-    EditInteractionResponse
+    EditInteractionResponse,
 };
 use thiserror::Error;
 
 use crate::server_state::{ContextExt, ServerState};
 
+pub mod audit;
+pub mod backup;
+pub mod console;
+pub mod kill;
+pub mod links;
 pub mod log;
+pub mod permissions;
 pub mod ping;
+pub mod playtime;
 pub mod restart;
+pub mod schedule;
 pub mod snitch;
-
+pub mod start;
+pub mod stats;
+pub mod status;
+pub mod stop;
+pub mod subscriptions;
+pub mod whitelist;
 
 #[derive(Error, Debug)]
 pub enum CommandError {
@@ -27,6 +41,9 @@ pub enum CommandError {
     BadOptionPassed,
     #[error("Invalid option index accessed: {0}")]
     BadOptionIndex(u8),
+    /// The command ran and the user was told it failed, kept for the audit log
+    #[error("{0}")]
+    Operation(String),
 }
 pub type CommandResult = Result<(), CommandError>;
 
@@ -53,6 +70,22 @@ impl Context {
         Ok(())
     }
 
+    /// Replies so that only the user who ran the command sees it.
+    pub async fn say_ephemeral(&self, str: impl Into<String>) -> CommandResult {
+        self.command
+            .create_response(
+                &self.context.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(str)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_msg(&self, str: impl Into<String>) -> CommandResult {
         self.command
             .edit_response(
@@ -69,3 +102,26 @@ impl Context {
     }
 }
 
+pub fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes:02}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::format_duration;
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(TimeDelta::zero()), "0m");
+        assert_eq!(format_duration(TimeDelta::seconds(59)), "0m");
+        assert_eq!(format_duration(TimeDelta::minutes(42)), "42m");
+        assert_eq!(format_duration(TimeDelta::minutes(65)), "1h 05m");
+        assert_eq!(format_duration(TimeDelta::hours(100)), "100h 00m");
+    }
+}
@@ -0,0 +1,250 @@
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, Permissions, ResolvedOption,
+    ResolvedValue, RoleId,
+};
+
+use crate::sql::permissions::{Capability, RolePermission};
+
+use super::{CommandError, CommandResult, Context};
+
+/// The capability a command needs, or `None` if anyone may run it.
+///
+/// Scheduling an action needs the capability of running it, `action` is the action of
+/// `/schedule add`.
+fn required_capability(command_name: &str, action: Option<&str>) -> Option<Capability> {
+    match (command_name, action) {
+        ("restart", _) | ("schedule", Some("restart")) => Some(Capability::Restart),
+        ("start" | "stop" | "kill", _) => Some(Capability::StartStop),
+        ("log", _) => Some(Capability::Logs),
+        ("console", _) | ("schedule", Some("console")) => Some(Capability::Console),
+        ("whitelist", _) => Some(Capability::Whitelist),
+        ("backup", _) => Some(Capability::Backup),
+        _ => None,
+    }
+}
+
+/// The action of `/schedule add`.
+fn scheduled_action<'a>(options: &[ResolvedOption<'a>]) -> Option<&'a str> {
+    match options.first() {
+        Some(ResolvedOption {
+            name: "add",
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) => options
+            .iter()
+            .find_map(|option| match (option.name, &option.value) {
+                ("action", ResolvedValue::String(action)) => Some(*action),
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+/// Whether the user may run the command, checked before any command is dispatched.
+///
+/// Administrators can run everything, so a guild is never locked out of its own bot.
+pub async fn is_allowed(ctx: &Context) -> Result<bool, CommandError> {
+    let options = ctx.command.data.options();
+    let Some(capability) = required_capability(&ctx.command.data.name, scheduled_action(&options))
+    else {
+        return Ok(true);
+    };
+    let (Some(guild_id), Some(member)) = (ctx.command.guild_id, &ctx.command.member) else {
+        return Ok(false);
+    };
+    if member
+        .permissions
+        .is_some_and(|permissions| permissions.administrator())
+    {
+        return Ok(true);
+    }
+
+    Ok(RolePermission::any_role_has(
+        &ctx.get_server_state().await.db,
+        guild_id,
+        &member.roles,
+        capability,
+    )
+    .await?)
+}
+
+fn role_and_capability(options: &[ResolvedOption]) -> Option<(RoleId, Capability)> {
+    let mut role_id = None;
+    let mut capability = None;
+    for option in options {
+        match (option.name, &option.value) {
+            ("role", ResolvedValue::Role(role)) => role_id = Some(role.id),
+            ("capability", ResolvedValue::String(s)) => capability = Capability::parse(s),
+            _ => return None,
+        }
+    }
+    Some((role_id?, capability?))
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+    let options = ctx.command.data.options();
+    let subcommand = options.first().ok_or(CommandError::BadOptionIndex(0))?;
+    let db = &ctx.get_server_state().await.db;
+
+    match (subcommand.name, &subcommand.value) {
+        ("grant", ResolvedValue::SubCommand(options)) => {
+            let (role_id, capability) =
+                role_and_capability(options).ok_or(CommandError::BadOptionPassed)?;
+            RolePermission::new(guild_id, role_id, capability)
+                .insert()
+                .execute(db)
+                .await?;
+            ctx.say_ephemeral(format!(
+                "<@&{role_id}> can now use `{}` commands",
+                capability.name()
+            ))
+            .await?;
+        }
+        ("revoke", ResolvedValue::SubCommand(options)) => {
+            let (role_id, capability) =
+                role_and_capability(options).ok_or(CommandError::BadOptionPassed)?;
+            RolePermission::new(guild_id, role_id, capability)
+                .remove()
+                .execute(db)
+                .await?;
+            ctx.say_ephemeral(format!(
+                "<@&{role_id}> can no longer use `{}` commands",
+                capability.name()
+            ))
+            .await?;
+        }
+        ("list", ResolvedValue::SubCommand(_)) => {
+            let granted = RolePermission::get_for_guild(db, guild_id).await?;
+            let mut lines: Vec<String> = Vec::new();
+            let mut last_role = None;
+            for granted in granted {
+                let role_id = granted.role_id.get();
+                if last_role == Some(role_id) {
+                    let line = lines.last_mut().expect("a line per role");
+                    line.push_str(&format!(", `{}`", granted.capability));
+                } else {
+                    lines.push(format!("<@&{role_id}>: `{}`", granted.capability));
+                    last_role = Some(role_id);
+                }
+            }
+
+            if lines.is_empty() {
+                ctx.say_ephemeral("No roles have been granted anything, only administrators can use restricted commands")
+                    .await?;
+            } else {
+                ctx.say_ephemeral(format!(
+                    "Administrators can use every command. Other roles:\n{}",
+                    lines.join("\n")
+                ))
+                .await?;
+            }
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    let role_and_capability = |name: &str, description: &str| {
+        let mut capability =
+            CreateCommandOption::new(CommandOptionType::String, "capability", "Commands to allow")
+                .required(true);
+        for (name, _) in Capability::CHOICES {
+            capability = capability.add_string_choice(name, name);
+        }
+
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Role, "role", "Discord role")
+                    .required(true),
+            )
+            .add_sub_option(capability)
+    };
+
+    CreateCommand::new("permissions")
+        .description("Manage which roles can use the server commands")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(role_and_capability(
+            "grant",
+            "Let a role use a group of commands",
+        ))
+        .add_option(role_and_capability(
+            "revoke",
+            "Stop a role from using a group of commands",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show which roles can use which commands",
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::permissions::Capability;
+
+    use super::required_capability;
+
+    #[test]
+    fn restricted_commands() {
+        // Restarting has its own capability
+        assert_eq!(
+            required_capability("restart", None),
+            Some(Capability::Restart)
+        );
+
+        // Starting, stopping and killing share one
+        assert_eq!(
+            required_capability("start", None),
+            Some(Capability::StartStop)
+        );
+        assert_eq!(
+            required_capability("stop", None),
+            Some(Capability::StartStop)
+        );
+        assert_eq!(
+            required_capability("kill", None),
+            Some(Capability::StartStop)
+        );
+
+        // Reading logs
+        assert_eq!(required_capability("log", None), Some(Capability::Logs));
+
+        // Running console commands
+        assert_eq!(
+            required_capability("console", None),
+            Some(Capability::Console)
+        );
+
+        // Managing the whitelist
+        assert_eq!(
+            required_capability("whitelist", None),
+            Some(Capability::Whitelist)
+        );
+
+        // Managing backups
+        assert_eq!(
+            required_capability("backup", None),
+            Some(Capability::Backup)
+        );
+
+        // Read only commands are open to everyone
+        assert_eq!(required_capability("status", None), None);
+        assert_eq!(required_capability("playtime", None), None);
+        assert_eq!(required_capability("ping", None), None);
+
+        // Scheduling needs the capability of the action
+        assert_eq!(
+            required_capability("schedule", Some("restart")),
+            Some(Capability::Restart)
+        );
+        assert_eq!(
+            required_capability("schedule", Some("console")),
+            Some(Capability::Console)
+        );
+        assert_eq!(required_capability("schedule", Some("message")), None);
+        assert_eq!(required_capability("schedule", None), None);
+    }
+}
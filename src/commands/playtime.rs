@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption, ResolvedValue};
+
+use crate::sql::{links::AccountLink, sessions::PlaySession};
+
+use super::{CommandError, CommandResult, Context, format_duration};
+
+/// Discord messages are capped at 2000 characters
+const MAX_DAYS_SHOWN: usize = 31;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl Period {
+    const CHOICES: [(&str, Period); 4] = [
+        ("day", Period::Day),
+        ("week", Period::Week),
+        ("month", Period::Month),
+        ("all", Period::All),
+    ];
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::CHOICES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, period)| *period)
+    }
+
+    fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Period::Day => now - TimeDelta::days(1),
+            Period::Week => now - TimeDelta::days(7),
+            Period::Month => now - TimeDelta::days(30),
+            Period::All => DateTime::UNIX_EPOCH,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Period::Day => "in the last 24 hours",
+            Period::Week => "in the last 7 days",
+            Period::Month => "in the last 30 days",
+            Period::All => "in total",
+        }
+    }
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let mut player_name = None;
+    let mut user_id = ctx.command.user.id;
+    let mut period = Period::Week;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("minecraft_name", ResolvedValue::String(s)) => player_name = Some(s.to_string()),
+            ("user", ResolvedValue::User(user, _)) => user_id = user.id,
+            ("period", ResolvedValue::String(s)) => {
+                period = Period::parse(s).ok_or(CommandError::BadOptionPassed)?
+            }
+            _ => return Err(CommandError::BadOptionPassed),
+        }
+    }
+
+    let db = &ctx.get_server_state().await.db;
+    let player_name = match player_name {
+        Some(player_name) => player_name,
+        None => match AccountLink::get_for_user(db, user_id).await? {
+            Some(link) => link.player_name,
+            None => {
+                ctx.say_ephemeral(format!(
+                    ":x: <@{user_id}> has not linked a Minecraft account, give a name instead"
+                ))
+                .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let now = Utc::now();
+    let from = period.start(now);
+    let sessions = PlaySession::get_since(db, &player_name, from).await?;
+
+    let per_day = playtime_per_day(&sessions, from, now);
+    let total = per_day.values().copied().sum::<TimeDelta>();
+    if total.is_zero() {
+        ctx.say(format!(
+            ":hourglass: {player_name} has not played {}.",
+            period.describe()
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let days: Vec<_> = per_day
+        .iter()
+        .rev()
+        .take(MAX_DAYS_SHOWN)
+        .map(|(day, time)| format!("{day}: {}", format_duration(*time)))
+        .collect();
+    let days: Vec<_> = days.into_iter().rev().collect();
+
+    let last_session = sessions.last().expect("some playtime means some sessions");
+    // Use the name as it appears in game rather than as typed
+    let player_name = &last_session.player_name;
+    let last_seen = match (last_session.ended_at, &last_session.end_reason) {
+        (None, _) => "Currently online".to_string(),
+        (Some(ended_at), Some(reason)) => {
+            format!("Last seen <t:{}:R> ({reason})", ended_at.timestamp())
+        }
+        (Some(ended_at), None) => format!("Last seen <t:{}:R>", ended_at.timestamp()),
+    };
+
+    ctx.say(format!(
+        ":hourglass: {player_name} has played for {} {}. {last_seen}\n```{}```",
+        format_duration(total),
+        period.describe(),
+        days.join("\n")
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Splits sessions at midnight (UTC) and sums them per day, clipped to `from..now`.
+/// Sessions that are still open count until `now`.
+fn playtime_per_day(
+    sessions: &[PlaySession],
+    from: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> BTreeMap<NaiveDate, TimeDelta> {
+    let mut per_day = BTreeMap::new();
+
+    for session in sessions {
+        let mut start = session.started_at.max(from);
+        let end = session.ended_at.unwrap_or(now).min(now);
+
+        while start < end {
+            let day = start.date_naive();
+            let next_day = day
+                .succ_opt()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc())
+                .unwrap_or(end);
+            let segment_end = end.min(next_day);
+
+            *per_day.entry(day).or_insert(TimeDelta::zero()) += segment_end - start;
+            start = segment_end;
+        }
+    }
+
+    per_day
+}
+
+pub fn register() -> CreateCommand {
+    let mut period = CreateCommandOption::new(
+        CommandOptionType::String,
+        "period",
+        "Time period to report on (default: week)",
+    );
+    for (name, _) in Period::CHOICES {
+        period = period.add_string_choice(name, name);
+    }
+
+    CreateCommand::new("playtime")
+        .description("Shows how long a player has been on the minecraft server")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "minecraft_name",
+            "Minecraft player name. Defaults to the linked account of the user",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::User,
+            "user",
+            "Discord user with a linked account. Defaults to you",
+        ))
+        .add_option(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDate, TimeDelta, TimeZone, Utc};
+
+    use crate::sql::sessions::PlaySession;
+
+    use super::{Period, playtime_per_day};
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 11, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 11, day).unwrap()
+    }
+
+    fn session(started_at: DateTime<Utc>, ended_at: Option<DateTime<Utc>>) -> PlaySession {
+        PlaySession {
+            player_name: "sally".to_string(),
+            started_at,
+            ended_at,
+            end_reason: None,
+        }
+    }
+
+    #[test]
+    fn sums_sessions_on_the_same_day() {
+        let sessions = [
+            session(at(10, 12, 0), Some(at(10, 13, 0))),
+            session(at(10, 18, 0), Some(at(10, 18, 30))),
+        ];
+        let per_day = playtime_per_day(&sessions, at(1, 0, 0), at(20, 0, 0));
+
+        assert_eq!(per_day.len(), 1);
+        assert_eq!(per_day[&date(10)], TimeDelta::minutes(90));
+    }
+
+    #[test]
+    fn splits_sessions_at_midnight() {
+        let sessions = [session(at(10, 23, 0), Some(at(11, 1, 30)))];
+        let per_day = playtime_per_day(&sessions, at(1, 0, 0), at(20, 0, 0));
+
+        assert_eq!(per_day[&date(10)], TimeDelta::minutes(60));
+        assert_eq!(per_day[&date(11)], TimeDelta::minutes(90));
+    }
+
+    #[test]
+    fn clips_to_period() {
+        let sessions = [session(at(9, 22, 0), Some(at(10, 2, 0)))];
+        let per_day = playtime_per_day(&sessions, at(10, 1, 0), at(20, 0, 0));
+
+        assert_eq!(per_day.len(), 1);
+        assert_eq!(per_day[&date(10)], TimeDelta::minutes(60));
+    }
+
+    #[test]
+    fn open_sessions_count_until_now() {
+        let sessions = [session(at(10, 12, 0), None)];
+        let per_day = playtime_per_day(&sessions, at(1, 0, 0), at(10, 12, 45));
+
+        assert_eq!(per_day[&date(10)], TimeDelta::minutes(45));
+    }
+
+    #[test]
+    fn ignores_sessions_before_period() {
+        let sessions = [session(at(2, 12, 0), Some(at(2, 13, 0)))];
+        let per_day = playtime_per_day(&sessions, at(5, 0, 0), at(20, 0, 0));
+
+        assert!(per_day.is_empty());
+    }
+
+    #[test]
+    fn parses_periods() {
+        assert_eq!(Period::parse("day"), Some(Period::Day));
+        assert_eq!(Period::parse("all"), Some(Period::All));
+        assert_eq!(Period::parse("year"), None);
+    }
+}
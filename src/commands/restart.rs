@@ -1,22 +1,191 @@
-use serenity::all::CreateCommand;
+use std::time::Duration;
 
-use crate::docker::restart_server;
+use serenity::all::{
+    ButtonStyle, CommandOptionType, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditInteractionResponse, ResolvedValue,
+};
 
-use super::{CommandResult, Context};
+use crate::{lifecycle, server_state::ContextExt};
+
+use super::{CommandError, CommandResult, Context, audit};
+
+pub const CONFIRM_ID: &str = "restart_confirm";
+pub const CANCEL_ID: &str = "restart_cancel";
+const MAX_DELAY_MINUTES: i64 = 24 * 60;
+
+/// The `in` minutes and whether to cancel, if the options make sense.
+fn options(ctx: &Context) -> Option<(Option<i64>, bool)> {
+    let mut minutes = None;
+    let mut cancel = false;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("in", ResolvedValue::Integer(i)) => minutes = Some(i.clamp(1, MAX_DELAY_MINUTES)),
+            ("cancel", ResolvedValue::Boolean(b)) => cancel = b,
+            _ => return None,
+        }
+    }
+    Some((minutes, cancel))
+}
+
+/// Whether this is a plain `/restart`, which only asks for confirmation. The button press is
+/// what gets audited.
+pub fn asks_confirmation(ctx: &Context) -> bool {
+    ctx.command.data.name == "restart" && options(ctx) == Some((None, false))
+}
 
 pub async fn run(ctx: &Context) -> CommandResult {
-    ctx.say(":arrows_clockwise: Restarting Server..").await?;
+    let (minutes, cancel) = options(ctx).ok_or(CommandError::BadOptionPassed)?;
+
+    let server_state = ctx.get_server_state().await;
+    if cancel {
+        let msg = match lifecycle::cancel_scheduled_restart(&server_state).await {
+            Some(at) => format!(
+                ":x: Cancelled the restart planned for <t:{}:T>",
+                at.timestamp()
+            ),
+            None => "There is no restart scheduled".to_string(),
+        };
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    if let Some(minutes) = minutes {
+        let delay = Duration::from_secs(minutes as u64 * 60);
+        let result =
+            lifecycle::schedule_restart(&server_state, ctx.context.http.clone(), delay).await;
+        let msg = match &result {
+            Ok(at) => format!(
+                ":timer: Server will restart <t:{}:R>, players are being warned in game. Use `/restart cancel:True` to call it off",
+                at.timestamp()
+            ),
+            Err(e) => format!("Failed to schedule restart:\n{e}"),
+        };
+        ctx.say(msg).await?;
+        return result
+            .map(|_| ())
+            .map_err(|e| CommandError::Operation(e.to_string()));
+    }
 
-    let msg = if let Err(e) = restart_server(ctx.get_server_state().await.as_ref()).await {
-        format!("Failed to restart:\n{e}")
-    } else {
-        ":white_check_mark: Server restarted!".to_string()
+    let online = server_state.mutables.read().await.online_players.len();
+    let user_id = ctx.command.user.id;
+    ctx.command
+        .create_response(
+            &ctx.context.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!(
+                        ":warning: Restart the server now? {online} players are online. Use `/restart in:<minutes>` to warn them first"
+                    ))
+                    .components(vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new(format!("{CONFIRM_ID}:{user_id}"))
+                            .label("Restart now")
+                            .style(ButtonStyle::Danger),
+                        CreateButton::new(format!("{CANCEL_ID}:{user_id}"))
+                            .label("Cancel")
+                            .style(ButtonStyle::Secondary),
+                    ])]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Handles the buttons under the confirmation, which only the user who asked may press.
+pub async fn handle_component(
+    ctx: &serenity::all::Context,
+    component: &ComponentInteraction,
+) -> CommandResult {
+    let Some((action, user_id)) = component.data.custom_id.split_once(':') else {
+        return Err(CommandError::BadOptionPassed);
     };
-    ctx.update_msg(msg).await?;
+    if user_id != component.user.id.to_string() {
+        component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Only the person who ran /restart can answer this")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let update = |content: &str| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(vec![]),
+        )
+    };
+    match action {
+        CANCEL_ID => {
+            component
+                .create_response(&ctx.http, update("Restart cancelled"))
+                .await?;
+        }
+        CONFIRM_ID => {
+            component
+                .create_response(&ctx.http, update(":arrows_clockwise: Restarting Server.."))
+                .await?;
+
+            let server_state = ctx.get_server_state().await;
+            let result = lifecycle::restart(&server_state).await;
+            let outcome = match &result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => e.to_string(),
+            };
+            if let Err(e) = audit::write(
+                &server_state,
+                component.guild_id,
+                component.user.id,
+                &component.user.name,
+                "restart",
+                "confirmed".to_string(),
+                &outcome,
+            )
+            .await
+            {
+                log::error!("Cannot write audit log: {e}");
+            }
+
+            let msg = if let Err(e) = result {
+                format!("Failed to restart:\n{e}")
+            } else {
+                ":white_check_mark: Server restarted!".to_string()
+            };
+            component
+                .edit_response(&ctx.http, EditInteractionResponse::new().content(msg))
+                .await?;
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
 
     Ok(())
 }
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("restart").description("Restarts the minecraft server!")
+    CreateCommand::new("restart")
+        .description("Restarts the minecraft server!")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "in",
+                "Minutes to count down in game before restarting",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_DELAY_MINUTES as u64)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "cancel",
+                "Call off a restart started with in",
+            )
+            .required(false),
+        )
 }
@@ -0,0 +1,210 @@
+use chrono::{NaiveTime, Utc};
+use chrono_tz::Tz;
+use serenity::all::{
+    ChannelId, CommandOptionType, CreateCommand, CreateCommandOption, GuildId, Permissions,
+    ResolvedOption, ResolvedValue, UserId,
+};
+
+use crate::{
+    console,
+    scheduler::{self, DAYS, Schedule, ScheduledAction},
+    sql::schedules::ScheduleRow,
+};
+
+use super::{CommandError, CommandResult, Context};
+
+/// Reads the options of `/schedule add`, or says what is wrong with them.
+fn parse_add(
+    options: &[ResolvedOption],
+    guild_id: GuildId,
+    user_id: UserId,
+    channel_id: ChannelId,
+) -> Result<Schedule, String> {
+    let mut action = None;
+    let mut time = None;
+    let mut weekday = None;
+    let mut timezone = Tz::UTC;
+    let mut text = None;
+    let mut target_channel = channel_id;
+    for option in options {
+        match (option.name, &option.value) {
+            ("action", ResolvedValue::String(s)) => action = Some(*s),
+            ("time", ResolvedValue::String(s)) => {
+                time = Some(
+                    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                        .map_err(|_| format!("`{s}` is not a time like 05:00"))?,
+                )
+            }
+            ("day", ResolvedValue::String(s)) => {
+                weekday = DAYS
+                    .iter()
+                    .find(|(name, _)| name == s)
+                    .map(|(_, weekday)| *weekday)
+            }
+            ("timezone", ResolvedValue::String(s)) => {
+                timezone = s.trim().parse().map_err(|_| {
+                    format!("`{s}` is not a timezone, use a name like Europe/Stockholm")
+                })?
+            }
+            ("text", ResolvedValue::String(s)) => text = Some(s.to_string()),
+            ("channel", ResolvedValue::Channel(channel)) => target_channel = channel.id,
+            _ => return Err("Unknown option".to_string()),
+        }
+    }
+
+    let name = action.ok_or("An action is required")?;
+    let action = ScheduledAction::new(name, text, Some(target_channel))
+        .ok_or_else(|| format!("The {name} action needs the text option"))?;
+    Ok(Schedule {
+        id: 0,
+        guild_id,
+        created_by: user_id,
+        weekday: weekday.ok_or("A day is required")?,
+        time: time.ok_or("A time is required")?,
+        timezone,
+        action,
+        // Only times after the schedule was made count
+        last_run_at: Utc::now(),
+    })
+}
+
+fn describe(schedule: &Schedule) -> String {
+    let when = match schedule.weekday {
+        None => "Every day".to_string(),
+        Some(_) => format!("Every {}", scheduler::weekday_name(schedule.weekday)),
+    };
+    format!(
+        "`#{}` {when} at {} {}: {}, next <t:{}:R>",
+        schedule.id,
+        schedule.time.format("%H:%M"),
+        schedule.timezone.name(),
+        schedule.action.describe(),
+        schedule.next_run(Utc::now()).timestamp()
+    )
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+    let options = ctx.command.data.options();
+    let subcommand = options.first().ok_or(CommandError::BadOptionIndex(0))?;
+    let server_state = ctx.get_server_state().await;
+
+    match (subcommand.name, &subcommand.value) {
+        ("add", ResolvedValue::SubCommand(options)) => {
+            let schedule = match parse_add(
+                options,
+                guild_id,
+                ctx.command.user.id,
+                ctx.command.channel_id,
+            ) {
+                Ok(schedule) => schedule,
+                Err(e) => {
+                    ctx.say_ephemeral(format!(":x: {e}")).await?;
+                    return Ok(());
+                }
+            };
+            // Checked again when it runs, the lists may change in the meantime
+            if let ScheduledAction::Console(command) = &schedule.action
+                && let Err(e) = console::check_permitted(&server_state.bot_config, command)
+            {
+                ctx.say_ephemeral(format!(":no_entry: {e}")).await?;
+                return Err(CommandError::Operation(e.to_string()));
+            }
+            let id = schedule.to_row(Utc::now()).insert(&server_state.db).await?;
+            ctx.say(format!(
+                ":calendar: Added schedule {}",
+                describe(&Schedule { id, ..schedule })
+            ))
+            .await?;
+        }
+        ("remove", ResolvedValue::SubCommand(options)) => {
+            let id = match options.first().map(|option| &option.value) {
+                Some(ResolvedValue::Integer(id)) => *id,
+                _ => return Err(CommandError::BadOptionPassed),
+            };
+            let sql_res = ScheduleRow::remove(guild_id, id)
+                .execute(&server_state.db)
+                .await?;
+            if sql_res.rows_affected() == 1 {
+                ctx.say(format!("Removed schedule `#{id}`")).await?;
+            } else {
+                ctx.say(format!("There is no schedule `#{id}`")).await?;
+            }
+        }
+        ("list", ResolvedValue::SubCommand(_)) => {
+            let schedules = Schedule::get_for_guild(&server_state, guild_id).await?;
+            if schedules.is_empty() {
+                ctx.say("Nothing is scheduled, add something with `/schedule add`")
+                    .await?;
+            } else {
+                let lines: Vec<_> = schedules.iter().map(describe).collect();
+                ctx.say(lines.join("\n")).await?;
+            }
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    let mut action =
+        CreateCommandOption::new(CommandOptionType::String, "action", "What to do").required(true);
+    for name in ScheduledAction::NAMES {
+        action = action.add_string_choice(name, name);
+    }
+    let mut day =
+        CreateCommandOption::new(CommandOptionType::String, "day", "Which days to run on")
+            .required(true);
+    for (name, _) in DAYS {
+        day = day.add_string_choice(name, name);
+    }
+
+    CreateCommand::new("schedule")
+        .description("Run actions at set times every day or week")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Add a schedule")
+                .add_sub_option(action)
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "time",
+                        "Time of day, like 05:00",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(day)
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "text",
+                    "Console command or message to post",
+                ))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "timezone",
+                    "Timezone of the time, like Europe/Stockholm. Defaults to UTC",
+                ))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "Channel to post the message in. Defaults to this one",
+                )),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a schedule")
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "id",
+                        "Number shown by /schedule list",
+                    )
+                    .required(true),
+                ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the schedules",
+        ))
+}
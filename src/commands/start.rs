@@ -0,0 +1,25 @@
+use serenity::all::CreateCommand;
+
+use crate::lifecycle;
+
+use super::{CommandError, CommandResult, Context};
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    ctx.say(":arrow_forward: Starting Server..").await?;
+
+    let server_state = ctx.get_server_state().await;
+    let result = lifecycle::start(&server_state).await;
+    let msg = if let Err(e) = &result {
+        format!("Failed to start:\n{e}")
+    } else {
+        ":white_check_mark: Server container started, it will be up once the world has loaded"
+            .to_string()
+    };
+    ctx.update_msg(msg).await?;
+
+    result.map_err(|e| CommandError::Operation(e.to_string()))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("start").description("Starts the minecraft server")
+}
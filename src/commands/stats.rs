@@ -0,0 +1,160 @@
+use chrono::{TimeDelta, Utc};
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption, ResolvedValue};
+
+use crate::{docker::container_stats, sql::stats::StatsSample};
+
+use super::{CommandError, CommandResult, Context};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Hour,
+    Day,
+}
+
+impl Period {
+    const CHOICES: [(&str, Period); 2] = [("hour", Period::Hour), ("day", Period::Day)];
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::CHOICES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, period)| *period)
+    }
+
+    fn duration(&self) -> TimeDelta {
+        match self {
+            Period::Hour => TimeDelta::hours(1),
+            Period::Day => TimeDelta::days(1),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Period::Hour => "Last hour",
+            Period::Day => "Last 24 hours",
+        }
+    }
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let mut period = Period::Hour;
+    for option in ctx.command.data.options() {
+        match (option.name, option.value) {
+            ("period", ResolvedValue::String(s)) => {
+                period = Period::parse(s).ok_or(CommandError::BadOptionPassed)?
+            }
+            _ => return Err(CommandError::BadOptionPassed),
+        }
+    }
+
+    let server_state = ctx.get_server_state().await;
+    let mut msg = ":bar_chart: **Server resources**\n".to_string();
+    match container_stats(&server_state).await {
+        Ok(Some(stats)) => {
+            msg.push_str(&format!("CPU: {:.1}%\n", stats.cpu_percent));
+            msg.push_str(&format!(
+                "Memory: {} / {}",
+                format_bytes(stats.memory_usage),
+                format_bytes(stats.memory_limit)
+            ));
+            if stats.memory_limit > 0 {
+                msg.push_str(&format!(
+                    " ({:.0}%)",
+                    stats.memory_usage as f64 / stats.memory_limit as f64 * 100.0
+                ));
+            }
+            msg.push_str(&format!(
+                "\nNetwork: {} in, {} out\nDisk: {} read, {} written\n",
+                format_bytes(stats.network_rx),
+                format_bytes(stats.network_tx),
+                format_bytes(stats.block_read),
+                format_bytes(stats.block_write)
+            ));
+        }
+        Ok(None) => msg.push_str(":x: Docker did not return any stats\n"),
+        Err(e) => {
+            log::error!("Could not get container stats: {e}");
+            msg.push_str(":x: Could not get stats from docker\n");
+        }
+    }
+
+    let summary =
+        StatsSample::summary_since(&server_state.db, Utc::now() - period.duration()).await?;
+    msg.push('\n');
+    if summary.samples == 0 {
+        msg.push_str(&format!("{}: no history recorded yet", period.describe()));
+    } else {
+        msg.push_str(&format!(
+            "{} ({} samples), min / avg / max:\nCPU: {:.1}% / {:.1}% / {:.1}%\nMemory: {} / {} / {}",
+            period.describe(),
+            summary.samples,
+            summary.cpu_min.unwrap_or_default(),
+            summary.cpu_avg.unwrap_or_default(),
+            summary.cpu_max.unwrap_or_default(),
+            format_bytes(summary.memory_min.unwrap_or_default() as u64),
+            format_bytes(summary.memory_avg.unwrap_or_default() as u64),
+            format_bytes(summary.memory_max.unwrap_or_default() as u64)
+        ));
+    }
+
+    ctx.say(msg).await?;
+
+    Ok(())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+pub fn register() -> CreateCommand {
+    let mut period = CreateCommandOption::new(
+        CommandOptionType::String,
+        "period",
+        "How far back to summarise (default: hour)",
+    )
+    .required(false);
+    for (name, _) in Period::CHOICES {
+        period = period.add_string_choice(name, name);
+    }
+
+    CreateCommand::new("stats")
+        .description("Shows the CPU, memory, network and disk use of the server container")
+        .add_option(period)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_bytes;
+
+    #[test]
+    fn formats_bytes() {
+        // Nothing
+        assert_eq!(format_bytes(0), "0 B");
+
+        // Just under a KiB
+        assert_eq!(format_bytes(1023), "1023 B");
+
+        // KiB, whole and fractional
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+
+        // MiB
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+
+        // GiB
+        assert_eq!(format_bytes(8 * 1024 * 1024 * 1024), "8.0 GiB");
+
+        // TiB is the largest unit
+        assert_eq!(format_bytes(3 * 1024_u64.pow(5)), "3072.0 TiB");
+    }
+}
@@ -0,0 +1,73 @@
+use chrono::Utc;
+use serenity::all::CreateCommand;
+use serenity::utils::MessageBuilder;
+
+use crate::{docker::container_status, lifecycle, server_ping};
+
+use super::{CommandResult, Context, format_duration};
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    ctx.say(":hourglass: Checking server status..").await?;
+
+    let server_state = ctx.get_server_state().await;
+    let (ping, container) = tokio::join!(
+        server_ping::ping(&server_state.bot_config.server_address),
+        container_status(&server_state)
+    );
+
+    let mut msg = MessageBuilder::new();
+    msg.push_line(format!(
+        "Server is **{}**",
+        lifecycle::state(&server_state).await
+    ));
+    match ping {
+        Ok(status) => {
+            msg.push_line(format!(
+                ":green_circle: **Server online** ({}, {}ms)",
+                status.version,
+                status.latency.as_millis()
+            ));
+            if !status.motd.trim().is_empty() {
+                msg.push_quote_line_safe(status.motd.trim());
+            }
+            msg.push(format!(
+                "Players: {}/{}",
+                status.online_players, status.max_players
+            ));
+            if !status.player_sample.is_empty() {
+                msg.push(" - ").push_safe(status.player_sample.join(", "));
+            }
+            msg.push_line("");
+        }
+        Err(e) => {
+            log::warn!("Could not ping server: {e}");
+            msg.push_line(":red_circle: **Server not responding**");
+        }
+    }
+
+    match container {
+        Ok(container) => {
+            msg.push(format!("Container: {}", container.status));
+            if let (true, Some(started_at)) = (container.running, container.started_at) {
+                msg.push(format!(" for {}", format_duration(Utc::now() - started_at)));
+            }
+            if let Some(health) = container.health {
+                msg.push(format!(", {health}"));
+            }
+            msg.push(format!(", restarted {} times", container.restart_count));
+        }
+        Err(e) => {
+            log::error!("Could not inspect container: {e}");
+            msg.push(":x: Could not inspect the container");
+        }
+    }
+
+    ctx.update_msg(msg.build()).await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("status")
+        .description("Shows whether the minecraft server is up and who is on")
+}
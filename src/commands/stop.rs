@@ -0,0 +1,25 @@
+use serenity::all::CreateCommand;
+
+use crate::lifecycle;
+
+use super::{CommandError, CommandResult, Context};
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    ctx.say(":stop_button: Saving the world and stopping Server..")
+        .await?;
+
+    let server_state = ctx.get_server_state().await;
+    let result = lifecycle::stop(&server_state).await;
+    let msg = if let Err(e) = &result {
+        format!("Failed to stop:\n{e}")
+    } else {
+        ":white_check_mark: Server stopped!".to_string()
+    };
+    ctx.update_msg(msg).await?;
+
+    result.map_err(|e| CommandError::Operation(e.to_string()))
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("stop").description("Saves the world and stops the minecraft server")
+}
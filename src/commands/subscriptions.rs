@@ -0,0 +1,89 @@
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, GuildId, Permissions, ResolvedValue,
+};
+
+use crate::sql::subscriptions::{EventSubscription, EventType};
+
+use super::{CommandError, CommandResult, Context};
+
+fn subscription(ctx: &Context, guild_id: GuildId) -> Option<EventSubscription> {
+    let event_type = match ctx.command.data.options().first()?.value {
+        ResolvedValue::String(s) => EventType::parse(s)?,
+        _ => return None,
+    };
+
+    Some(EventSubscription::new(
+        guild_id,
+        ctx.command.channel_id,
+        event_type,
+    ))
+}
+
+fn event_option() -> CreateCommandOption {
+    let mut option =
+        CreateCommandOption::new(CommandOptionType::String, "event", "What to announce")
+            .required(true);
+    for (name, _) in EventType::CHOICES {
+        option = option.add_string_choice(name, name);
+    }
+    option
+}
+
+pub mod subscribe {
+    use super::*;
+
+    pub async fn run(ctx: &Context) -> CommandResult {
+        let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+        let subscription = subscription(ctx, guild_id).ok_or(CommandError::BadOptionPassed)?;
+        let sql_res = subscription
+            .insert()
+            .execute(&ctx.get_server_state().await.db)
+            .await?;
+
+        let describe = subscription.event_type().describe();
+        if sql_res.rows_affected() == 1 {
+            ctx.say(format!("This channel will now announce {describe}"))
+                .await?;
+        } else {
+            ctx.say(format!("This channel already announces {describe}"))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn register() -> CreateCommand {
+        CreateCommand::new("subscribe")
+            .description("Announce an in-game event in this channel")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .add_option(event_option())
+    }
+}
+
+pub mod unsubscribe {
+    use super::*;
+
+    pub async fn run(ctx: &Context) -> CommandResult {
+        let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+        let subscription = subscription(ctx, guild_id).ok_or(CommandError::BadOptionPassed)?;
+        subscription
+            .remove()
+            .execute(&ctx.get_server_state().await.db)
+            .await?;
+
+        ctx.say(format!(
+            "This channel will no longer announce {}",
+            subscription.event_type().describe()
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn register() -> CreateCommand {
+        CreateCommand::new("unsubscribe")
+            .description("Stop announcing an in-game event in this channel")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .add_option(event_option())
+    }
+}
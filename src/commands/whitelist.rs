@@ -0,0 +1,203 @@
+use serenity::{
+    all::{CommandOptionType, CreateCommand, CreateCommandOption, ResolvedOption, ResolvedValue},
+    utils::MessageBuilder,
+};
+
+use crate::{
+    console,
+    log_parser::is_valid_player_name,
+    sql::{links::AccountLink, whitelist::WhitelistEntry},
+};
+
+use super::{CommandError, CommandResult, Context};
+
+fn player_name<'a>(options: &[ResolvedOption<'a>]) -> Option<&'a str> {
+    match options.first()?.value {
+        ResolvedValue::String(s) => Some(s.trim()),
+        _ => None,
+    }
+}
+
+/// Whether the server refused to whitelist the player, going by the console output.
+fn was_refused(output: &str) -> bool {
+    output.contains("That player does not exist")
+}
+
+/// Whether the player was not on the whitelist to begin with, going by the console output.
+fn was_not_whitelisted(output: &str) -> bool {
+    was_refused(output) || output.contains("Player is not whitelisted")
+}
+
+pub async fn run(ctx: &Context) -> CommandResult {
+    let guild_id = ctx.command.guild_id.ok_or(CommandError::BadGuildCall)?;
+    let options = ctx.command.data.options();
+    let subcommand = options.first().ok_or(CommandError::BadOptionIndex(0))?;
+    let server_state = ctx.get_server_state().await;
+
+    match (subcommand.name, &subcommand.value) {
+        ("add", ResolvedValue::SubCommand(options)) => {
+            let name = match player_name(options) {
+                Some(name) => name.to_string(),
+                None => {
+                    match AccountLink::get_for_user(&server_state.db, ctx.command.user.id).await? {
+                        Some(link) => link.player_name,
+                        None => {
+                            ctx.say_ephemeral(
+                                ":x: Give a Minecraft name, or link yours with `/link` first",
+                            )
+                            .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+            if !is_valid_player_name(&name) {
+                ctx.say_ephemeral(format!(":x: `{name}` is not a Minecraft name"))
+                    .await?;
+                return Ok(());
+            }
+
+            ctx.say(format!("Whitelisting {name}..")).await?;
+            let output =
+                match console::run_command(&server_state, &format!("whitelist add {name}")).await {
+                    Ok(output) => output,
+                    Err(e) => {
+                        ctx.update_msg(format!("Failed to whitelist {name}:\n{e}"))
+                            .await?;
+                        return Err(CommandError::Operation(e.to_string()));
+                    }
+                };
+            if was_refused(&output) {
+                ctx.update_msg(format!(":x: There is no Minecraft account called {name}"))
+                    .await?;
+                return Err(CommandError::Operation(format!("No account called {name}")));
+            }
+
+            WhitelistEntry::insert(guild_id, &name, ctx.command.user.id)
+                .execute(&server_state.db)
+                .await?;
+            ctx.update_msg(format!(
+                ":white_check_mark: {name} is whitelisted, requested by <@{}>",
+                ctx.command.user.id
+            ))
+            .await?;
+        }
+        ("remove", ResolvedValue::SubCommand(options)) => {
+            let name = player_name(options).ok_or(CommandError::BadOptionPassed)?;
+            if !is_valid_player_name(name) {
+                ctx.say_ephemeral(format!(":x: `{name}` is not a Minecraft name"))
+                    .await?;
+                return Ok(());
+            }
+
+            ctx.say(format!("Removing {name} from the whitelist.."))
+                .await?;
+            let output = match console::run_command(
+                &server_state,
+                &format!("whitelist remove {name}"),
+            )
+            .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    ctx.update_msg(format!("Failed to remove {name} from the whitelist:\n{e}"))
+                        .await?;
+                    return Err(CommandError::Operation(e.to_string()));
+                }
+            };
+            // Either way they are not on the server's whitelist anymore
+            WhitelistEntry::remove(guild_id, name)
+                .execute(&server_state.db)
+                .await?;
+            if was_not_whitelisted(&output) {
+                ctx.update_msg(format!(":x: {name} was not whitelisted"))
+                    .await?;
+                return Err(CommandError::Operation(format!(
+                    "{name} was not whitelisted"
+                )));
+            }
+            ctx.update_msg(format!(
+                ":white_check_mark: {name} is no longer whitelisted"
+            ))
+            .await?;
+        }
+        ("list", ResolvedValue::SubCommand(_)) => {
+            let entries = WhitelistEntry::get_for_guild(&server_state.db, guild_id).await?;
+            if entries.is_empty() {
+                ctx.say("Nobody has been whitelisted through the bot")
+                    .await?;
+                return Ok(());
+            }
+
+            let mut msg = MessageBuilder::new();
+            msg.push_line("Whitelisted through the bot:");
+            for entry in entries {
+                msg.push_safe(entry.player_name)
+                    .push(format!(
+                        " - <@{}> <t:{}:d>",
+                        entry.user_id.get(),
+                        entry.created_at.timestamp()
+                    ))
+                    .push("\n");
+            }
+            ctx.say(msg.build()).await?;
+        }
+        _ => return Err(CommandError::BadOptionPassed),
+    }
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("whitelist")
+        .description("Manage who can join the server")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add",
+                "Let a player join the server",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "name",
+                "Minecraft name. Defaults to your linked account",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "remove",
+                "Stop a player from joining the server",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "name", "Minecraft name")
+                    .required(true),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show who was whitelisted and by whom",
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_outputs() {
+        // No such account
+        let output = "That player does not exist";
+        assert!(was_refused(output));
+        assert!(was_not_whitelisted(output));
+
+        // Added, or already on the list
+        assert!(!was_refused("Added sally to the whitelist"));
+        assert!(!was_refused("Player is already whitelisted"));
+
+        // Removing someone who was not on the list
+        assert!(was_not_whitelisted("Player is not whitelisted"));
+        assert!(!was_not_whitelisted("Removed sally from the whitelist"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Instant,
+};
+
+use crate::{
+    docker,
+    log_parser::{self, ServerEvent},
+    rcon::RconError,
+    server_state::{BotConfig, ServerState},
+};
+
+/// Lines kept for listeners that fall behind, a busy server logs a lot at once
+pub const LINE_BUFFER: usize = 256;
+/// Commands that do not answer at all are given up on after this
+const FIRST_OUTPUT_TIMEOUT: Duration = Duration::from_secs(2);
+/// The output is over once the console has been quiet this long
+const OUTPUT_QUIET: Duration = Duration::from_millis(300);
+const MAX_OUTPUT_WAIT: Duration = Duration::from_secs(5);
+/// Commands that run other commands, which the allow and deny lists cannot fully see into
+const NESTING_COMMANDS: [&str; 2] = ["execute", "function"];
+
+#[derive(Error, Debug)]
+pub enum ConsoleError {
+    #[error("RCON error: {0}")]
+    Rcon(#[from] RconError),
+    #[error("Docker error: {0}")]
+    Docker(#[from] bollard::errors::Error),
+    #[error("`{0}` cannot be run from Discord")]
+    NotPermitted(String),
+}
+
+/// The name of a command, like `whitelist` for `/minecraft:whitelist`.
+fn normalise(name: &str) -> String {
+    let name = name.trim_start_matches('/');
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    name.to_lowercase()
+}
+
+/// Every command a command line runs, like `execute` and `stop` for `execute as @a run stop`.
+pub fn command_names(command: &str) -> Vec<String> {
+    let words: Vec<_> = command.split_whitespace().collect();
+    let mut names = vec![normalise(words.first().copied().unwrap_or_default())];
+    // Anything after a `run` may be a command, seeing a few too many only makes the lists stricter
+    for pair in words.windows(2) {
+        if pair[0] == "run" {
+            names.push(normalise(pair[1]));
+        }
+    }
+    names
+}
+
+/// The first command in `command` the lists rule out. `execute` and `function` have to be
+/// allowed by name, since they can run commands the lists never see.
+fn refused_command(command: &str, allowed: Option<&[String]>, denied: &[String]) -> Option<String> {
+    command_names(command).into_iter().find(|name| {
+        let listed = |list: &[String]| list.iter().any(|command| command == name);
+        let allowed = match allowed {
+            Some(allowed) => listed(allowed),
+            None => !NESTING_COMMANDS.contains(&name.as_str()),
+        };
+        !allowed || listed(denied)
+    })
+}
+
+/// Refuses commands CONSOLE_ALLOWED_COMMANDS and CONSOLE_DENIED_COMMANDS rule out.
+pub fn check_permitted(bot_config: &BotConfig, command: &str) -> Result<(), ConsoleError> {
+    match refused_command(
+        command,
+        bot_config.console_allowed_commands.as_deref(),
+        &bot_config.console_denied_commands,
+    ) {
+        Some(name) => Err(ConsoleError::NotPermitted(name)),
+        None => Ok(()),
+    }
+}
+
+/// Runs a command on the server console, over RCON when it is configured and through the
+/// container's stdin otherwise. Only RCON returns the output of the command.
+pub async fn send_command(
+    server_state: &ServerState,
+    command: &str,
+) -> Result<Option<String>, ConsoleError> {
+    match &server_state.rcon {
+        Some(rcon) => Ok(Some(rcon.command(command).await?)),
+        None => {
+            docker::send_command(server_state, command).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Runs a command on the server console and returns its output.
+///
+/// The console has no idea which lines belong to which command, so without RCON the output is
+/// whatever the server logs straight after, leaving out what the players are doing.
+pub async fn run_command(
+    server_state: &ServerState,
+    command: &str,
+) -> Result<String, ConsoleError> {
+    if let Some(rcon) = &server_state.rcon {
+        return Ok(rcon.command(command).await?);
+    }
+
+    let mut lines = server_state.console_lines.subscribe();
+    docker::send_command(server_state, command).await?;
+    Ok(collect_output(&mut lines).await.join("\n"))
+}
+
+async fn collect_output(lines: &mut broadcast::Receiver<String>) -> Vec<String> {
+    let deadline = Instant::now() + MAX_OUTPUT_WAIT;
+    let mut output = Vec::new();
+    loop {
+        let wait = match output.is_empty() {
+            true => FIRST_OUTPUT_TIMEOUT,
+            false => OUTPUT_QUIET,
+        };
+        let wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+        match tokio::time::timeout(wait, lines.recv()).await {
+            Ok(Ok(line)) => output.extend(output_message(&line)),
+            Ok(Err(RecvError::Lagged(skipped))) => {
+                log::warn!("Missed {skipped} console lines while waiting for command output")
+            }
+            Ok(Err(RecvError::Closed)) | Err(_) => return output,
+        }
+    }
+}
+
+/// The message of a console line, unless a player caused it.
+fn output_message(line: &str) -> Option<String> {
+    match log_parser::parse(line).event {
+        ServerEvent::CommandOutput { message }
+        | ServerEvent::Warning { message }
+        | ServerEvent::Unknown { message } => Some(message),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_messages() {
+        // Vanilla header
+        let s = "[12:00:00] [Server thread/INFO]: There are 1 of a max of 20 players online: sally";
+        assert_eq!(
+            output_message(s).as_deref(),
+            Some("There are 1 of a max of 20 players online: sally")
+        );
+
+        // Output the log parser knows nothing about
+        let s = "[12:00:00] [Server thread/INFO]: Teleported sally to 0.5, 64.0, 0.5";
+        assert_eq!(
+            output_message(s).as_deref(),
+            Some("Teleported sally to 0.5, 64.0, 0.5")
+        );
+
+        // Errors are logged as warnings
+        let s = "[12:00:00 WARN]: Unknown or incomplete command, see below for error";
+        assert_eq!(
+            output_message(s).as_deref(),
+            Some("Unknown or incomplete command, see below for error")
+        );
+
+        // Continuation line without a header
+        let s = "tp sally<--[HERE]";
+        assert_eq!(output_message(s).as_deref(), Some("tp sally<--[HERE]"));
+
+        // Chat happening at the same time
+        let s = "[12:00:00] [Server thread/INFO]: <sally> hello";
+        assert_eq!(output_message(s), None);
+
+        // Players joining at the same time
+        let s = "[12:00:00] [Server thread/INFO]: sally joined the game";
+        assert_eq!(output_message(s), None);
+    }
+
+    #[test]
+    fn names_of_nested_commands() {
+        assert_eq!(command_names("list"), vec!["list"]);
+        assert_eq!(command_names("/minecraft:TP sally 0 64 0"), vec!["tp"]);
+        assert_eq!(command_names("  say hi"), vec!["say"]);
+        assert_eq!(command_names(""), vec![""]);
+        assert_eq!(
+            command_names("execute as @s run execute at @s run minecraft:op bob"),
+            vec!["execute", "execute", "op"]
+        );
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let list = |commands: &[&str]| commands.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let allowed = list(&["list", "say", "stop", "execute"]);
+        let denied = list(&["stop", "op"]);
+
+        assert_eq!(refused_command("op bob", None, &[]), None);
+        assert_eq!(
+            refused_command("stop", None, &denied),
+            Some("stop".to_string())
+        );
+        assert_eq!(refused_command("say hi", Some(&allowed), &denied), None);
+        assert_eq!(
+            refused_command("whitelist add sally", Some(&allowed), &denied),
+            Some("whitelist".to_string())
+        );
+
+        // Denied commands cannot hide behind execute
+        assert_eq!(
+            refused_command("execute run stop", Some(&allowed), &denied),
+            Some("stop".to_string())
+        );
+        assert_eq!(
+            refused_command("execute as @s run op bob", Some(&allowed), &denied),
+            Some("op".to_string())
+        );
+        assert_eq!(
+            refused_command("execute as @a run say hi", Some(&allowed), &denied),
+            None
+        );
+
+        // They can only be allowed by name
+        assert_eq!(
+            refused_command("execute as @a run say hi", None, &denied),
+            Some("execute".to_string())
+        );
+        assert_eq!(
+            refused_command("function mypack:reset", None, &[]),
+            Some("function".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn collects_lines_until_quiet() {
+        let (sender, mut receiver) = broadcast::channel(LINE_BUFFER);
+        sender
+            .send("[12:00:00] [Server thread/INFO]: Whitelist is now turned on".to_string())
+            .unwrap();
+        sender
+            .send("[12:00:00] [Server thread/INFO]: <sally> hi".to_string())
+            .unwrap();
+        sender
+            .send("[12:00:00] [Server thread/INFO]: Reloaded the whitelist".to_string())
+            .unwrap();
+
+        assert_eq!(
+            collect_output(&mut receiver).await,
+            vec!["Whitelist is now turned on", "Reloaded the whitelist"]
+        );
+    }
+}
@@ -1,7 +1,17 @@
+use std::collections::HashMap;
+
 use bollard::query_parameters::{
-    AttachContainerOptionsBuilder, LogsOptionsBuilder, RestartContainerOptionsBuilder,
+    AttachContainerOptionsBuilder, CreateContainerOptions, DownloadFromContainerOptionsBuilder,
+    EventsOptionsBuilder, InspectContainerOptionsBuilder, KillContainerOptionsBuilder,
+    LogsOptionsBuilder, RemoveContainerOptionsBuilder, RestartContainerOptionsBuilder,
+    StartContainerOptions, StatsOptionsBuilder, StopContainerOptionsBuilder,
+    UploadToContainerOptionsBuilder, WaitContainerOptions,
 };
-use serenity::futures::StreamExt;
+use bollard::secret::{ContainerCreateBody, ContainerStatsResponse, EventMessage, HostConfig};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serenity::futures::{Stream, StreamExt};
+use tokio::io::AsyncWriteExt;
 
 use crate::ServerState;
 
@@ -20,16 +30,316 @@ pub async fn restart_server(server_state: &ServerState) -> Result<(), String> {
     Ok(())
 }
 
-pub async fn get_logs(global_data: &ServerState) -> (Vec<String>, Vec<bollard::errors::Error>) {
-    let logs = global_data.docker.logs(
-        &global_data.bot_config.container_name,
+pub async fn start_server(server_state: &ServerState) -> Result<(), String> {
+    let container_name = &server_state.bot_config.container_name;
+    log::info!("Starting container: {}", container_name);
+    server_state
+        .docker
+        .start_container(container_name, None::<StartContainerOptions>)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stops the container, giving the server `timeout_secs` to shut down before it is killed.
+pub async fn stop_server(server_state: &ServerState, timeout_secs: i32) -> Result<(), String> {
+    let container_name = &server_state.bot_config.container_name;
+    log::info!("Stopping container: {}", container_name);
+    server_state
+        .docker
+        .stop_container(
+            container_name,
+            Some(StopContainerOptionsBuilder::new().t(timeout_secs).build()),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn kill_server(server_state: &ServerState) -> Result<(), String> {
+    let container_name = &server_state.bot_config.container_name;
+    log::info!("Killing container: {}", container_name);
+    server_state
+        .docker
+        .kill_container(
+            container_name,
+            Some(KillContainerOptionsBuilder::new().signal("SIGKILL").build()),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub struct ContainerStatus {
+    /// One of "created", "running", "paused", "restarting", "removing", "exited" or "dead"
+    pub status: String,
+    pub running: bool,
+    pub started_at: Option<DateTime<Utc>>,
+    pub restart_count: i64,
+    pub health: Option<String>,
+    /// The image as it was asked for, like `itzg/minecraft-server:latest`
+    pub image: Option<String>,
+    /// Changes whenever the container is recreated from a different image, even under the same tag
+    pub image_id: Option<String>,
+}
+
+pub async fn container_status(
+    server_state: &ServerState,
+) -> Result<ContainerStatus, bollard::errors::Error> {
+    let inspect = server_state
+        .docker
+        .inspect_container(
+            &server_state.bot_config.container_name,
+            Some(InspectContainerOptionsBuilder::new().build()),
+        )
+        .await?;
+    let state = inspect.state.unwrap_or_default();
+
+    Ok(ContainerStatus {
+        status: state
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_default(),
+        running: state.running.unwrap_or(false),
+        started_at: state
+            .started_at
+            .and_then(|started_at| DateTime::parse_from_rfc3339(&started_at).ok())
+            .map(|started_at| started_at.to_utc()),
+        restart_count: inspect.restart_count.unwrap_or(0),
+        health: state
+            .health
+            .and_then(|health| health.status)
+            .map(|status| status.to_string()),
+        image: inspect.config.and_then(|config| config.image),
+        image_id: inspect.image,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerStats {
+    /// Percentage of one core, so a busy server on 4 cores can reach 400
+    pub cpu_percent: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+    pub network_rx: u64,
+    pub network_tx: u64,
+    pub block_read: u64,
+    pub block_write: u64,
+}
+
+impl ContainerStats {
+    /// Works out usage the way `docker stats` does.
+    fn from_response(stats: &ContainerStatsResponse) -> Self {
+        let cpu = stats.cpu_stats.clone().unwrap_or_default();
+        let precpu = stats.precpu_stats.clone().unwrap_or_default();
+        let total_usage = |cpu: &bollard::secret::ContainerCpuStats| {
+            cpu.cpu_usage
+                .as_ref()
+                .and_then(|usage| usage.total_usage)
+                .unwrap_or(0)
+        };
+        let cpu_delta = total_usage(&cpu).saturating_sub(total_usage(&precpu));
+        // The first sample of a stream has nothing to compare against
+        let system_delta = match (cpu.system_cpu_usage, precpu.system_cpu_usage) {
+            (Some(system), Some(presystem)) => system.saturating_sub(presystem),
+            _ => 0,
+        };
+        let online_cpus = cpu.online_cpus.unwrap_or(1);
+        let cpu_percent = if system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let memory = stats.memory_stats.clone().unwrap_or_default();
+        // Page cache can be reclaimed, so it does not count as used
+        let cache = memory
+            .stats
+            .as_ref()
+            .and_then(|stats| {
+                stats
+                    .get("inactive_file")
+                    .or_else(|| stats.get("total_inactive_file"))
+            })
+            .copied()
+            .unwrap_or(0);
+
+        let networks = stats.networks.clone().unwrap_or_default();
+        let block_io = stats
+            .blkio_stats
+            .as_ref()
+            .and_then(|blkio| blkio.io_service_bytes_recursive.clone())
+            .unwrap_or_default();
+        let block_total = |op: &str| {
+            block_io
+                .iter()
+                .filter(|entry| {
+                    entry
+                        .op
+                        .as_deref()
+                        .is_some_and(|entry_op| entry_op.eq_ignore_ascii_case(op))
+                })
+                .filter_map(|entry| entry.value)
+                .sum()
+        };
+
+        Self {
+            cpu_percent,
+            memory_usage: memory.usage.unwrap_or(0).saturating_sub(cache),
+            memory_limit: memory.limit.unwrap_or(0),
+            network_rx: networks.values().filter_map(|n| n.rx_bytes).sum(),
+            network_tx: networks.values().filter_map(|n| n.tx_bytes).sum(),
+            block_read: block_total("read"),
+            block_write: block_total("write"),
+        }
+    }
+}
+
+/// Takes one sample of the container's resource usage.
+pub async fn container_stats(
+    server_state: &ServerState,
+) -> Result<Option<ContainerStats>, bollard::errors::Error> {
+    let mut stats = server_state.docker.stats(
+        &server_state.bot_config.container_name,
+        Some(StatsOptionsBuilder::new().stream(false).build()),
+    );
+
+    Ok(stats
+        .next()
+        .await
+        .transpose()?
+        .map(|stats| ContainerStats::from_response(&stats)))
+}
+
+/// Streams docker events for the server container, including containers recreated under the same name.
+pub fn container_events(
+    server_state: &ServerState,
+) -> impl Stream<Item = Result<EventMessage, bollard::errors::Error>> + use<> {
+    let filters = HashMap::from([
+        ("type", vec!["container"]),
+        ("container", vec![server_state.bot_config.container_name.as_str()]),
+    ]);
+    server_state
+        .docker
+        .events(Some(EventsOptionsBuilder::new().filters(&filters).build()))
+}
+
+/// Streams a path out of the server container as a tar archive.
+pub fn download_archive(
+    server_state: &ServerState,
+    path: &str,
+) -> impl Stream<Item = Result<Bytes, bollard::errors::Error>> + use<> {
+    server_state.docker.download_from_container(
+        &server_state.bot_config.container_name,
         Some(
-            LogsOptionsBuilder::new()
-                .tail("20")
-                .stdout(true)
-                .stderr(true)
+            DownloadFromContainerOptionsBuilder::new()
+                .path(path)
                 .build(),
         ),
+    )
+}
+
+/// Extracts a tar archive into `parent_path` inside the server container.
+pub async fn upload_archive(
+    server_state: &ServerState,
+    parent_path: &str,
+    archive: impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static,
+) -> Result<(), bollard::errors::Error> {
+    server_state
+        .docker
+        .upload_to_container(
+            &server_state.bot_config.container_name,
+            Some(
+                UploadToContainerOptionsBuilder::new()
+                    .path(parent_path)
+                    .build(),
+            ),
+            bollard::body_try_stream(archive),
+        )
+        .await
+}
+
+/// Runs a shell script in a throwaway container from the server's image that shares its
+/// volumes, for moving files around while the server is stopped. Returns the exit code.
+pub async fn run_in_volumes(
+    server_state: &ServerState,
+    script: &str,
+) -> Result<i64, bollard::errors::Error> {
+    let container_name = &server_state.bot_config.container_name;
+    let image = container_status(server_state).await?.image;
+    let helper = server_state
+        .docker
+        .create_container(
+            None::<CreateContainerOptions>,
+            ContainerCreateBody {
+                image,
+                entrypoint: Some(vec!["sh".to_string(), "-c".to_string()]),
+                cmd: Some(vec![script.to_string()]),
+                host_config: Some(HostConfig {
+                    volumes_from: Some(vec![container_name.clone()]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+    log::debug!("Running in helper container {}: {script}", helper.id);
+
+    let result = async {
+        server_state
+            .docker
+            .start_container(&helper.id, None::<StartContainerOptions>)
+            .await?;
+        let mut wait = server_state
+            .docker
+            .wait_container(&helper.id, None::<WaitContainerOptions>);
+        match wait.next().await {
+            Some(Ok(response)) => Ok(response.status_code),
+            // Bollard reports a non zero exit as an error
+            Some(Err(bollard::errors::Error::DockerContainerWaitError { code, .. })) => Ok(code),
+            Some(Err(e)) => Err(e),
+            None => Ok(0),
+        }
+    }
+    .await;
+
+    if let Err(e) = server_state
+        .docker
+        .remove_container(
+            &helper.id,
+            Some(RemoveContainerOptionsBuilder::new().force(true).build()),
+        )
+        .await
+    {
+        log::warn!("Could not remove helper container {}: {e}", helper.id);
+    }
+    result
+}
+
+/// Whether docker failed because the container or path does not exist.
+pub fn is_not_found(error: &bollard::errors::Error) -> bool {
+    matches!(
+        error,
+        bollard::errors::Error::DockerResponseServerError {
+            status_code: 404,
+            ..
+        }
+    )
+}
+
+/// The last `lines` lines the container logged, only counting those after `since` if given.
+pub async fn get_logs(
+    global_data: &ServerState,
+    lines: usize,
+    since: Option<DateTime<Utc>>,
+) -> (Vec<String>, Vec<bollard::errors::Error>) {
+    let mut options = LogsOptionsBuilder::new()
+        .tail(&lines.to_string())
+        .stdout(true)
+        .stderr(true);
+    if let Some(since) = since {
+        options = options.since(since.timestamp() as i32);
+    }
+    let logs = global_data.docker.logs(
+        &global_data.bot_config.container_name,
+        Some(options.build()),
     );
 
     let (oks, errs): (Vec<_>, Vec<_>) = logs
@@ -51,6 +361,37 @@ pub async fn get_logs(global_data: &ServerState) -> (Vec<String>, Vec<bollard::e
     (ok_logs, errors)
 }
 
+/// Writes a command to the server console through the container's stdin.
+///
+/// The container has to be started with stdin open (`stdin_open: true` in compose).
+pub async fn send_command(
+    server_state: &ServerState,
+    command: &str,
+) -> Result<(), bollard::errors::Error> {
+    let container_name = &server_state.bot_config.container_name;
+    log::debug!("Sending command to container {container_name}: {command}");
+    let mut attachment = server_state
+        .docker
+        .attach_container(
+            container_name,
+            Some(
+                AttachContainerOptionsBuilder::new()
+                    .stdin(true)
+                    .stream(true)
+                    .build(),
+            ),
+        )
+        .await?;
+
+    attachment
+        .input
+        .write_all(format!("{command}\n").as_bytes())
+        .await?;
+    attachment.input.flush().await?;
+
+    Ok(())
+}
+
 pub async fn attach_and_listen<Fut>(
     server_state: &ServerState,
     func: impl Fn(String) -> Fut,
@@ -72,8 +413,7 @@ where
                     .build(),
             ),
         )
-        .await
-        .expect("Could not attach to the container");
+        .await?;
 
     while let Some(line) = attachment.output.next().await {
         let line = line?;
@@ -106,3 +446,94 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bollard::secret::{
+        ContainerBlkioStatEntry, ContainerBlkioStats, ContainerCpuStats, ContainerCpuUsage,
+        ContainerMemoryStats, ContainerNetworkStats, ContainerStatsResponse,
+    };
+
+    use super::ContainerStats;
+
+    fn cpu(total_usage: u64, system_cpu_usage: u64) -> Option<ContainerCpuStats> {
+        Some(ContainerCpuStats {
+            cpu_usage: Some(ContainerCpuUsage {
+                total_usage: Some(total_usage),
+                ..Default::default()
+            }),
+            system_cpu_usage: Some(system_cpu_usage),
+            online_cpus: Some(4),
+            ..Default::default()
+        })
+    }
+
+    fn block_io(op: &str, value: u64) -> ContainerBlkioStatEntry {
+        ContainerBlkioStatEntry {
+            op: Some(op.to_string()),
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    fn network(rx_bytes: u64, tx_bytes: u64) -> ContainerNetworkStats {
+        ContainerNetworkStats {
+            rx_bytes: Some(rx_bytes),
+            tx_bytes: Some(tx_bytes),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stats_from_response() {
+        let response = ContainerStatsResponse {
+            cpu_stats: cpu(1_500, 10_000),
+            precpu_stats: cpu(1_000, 8_000),
+            memory_stats: Some(ContainerMemoryStats {
+                usage: Some(3_000),
+                limit: Some(8_000),
+                stats: Some(HashMap::from([("inactive_file".to_string(), 1_000)])),
+                ..Default::default()
+            }),
+            networks: Some(HashMap::from([
+                ("eth0".to_string(), network(100, 200)),
+                ("eth1".to_string(), network(10, 20)),
+            ])),
+            blkio_stats: Some(ContainerBlkioStats {
+                io_service_bytes_recursive: Some(vec![
+                    block_io("read", 5),
+                    block_io("Write", 7),
+                    block_io("read", 1),
+                    block_io("sync", 100),
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ContainerStats::from_response(&response),
+            ContainerStats {
+                cpu_percent: 100.0,
+                memory_usage: 2_000,
+                memory_limit: 8_000,
+                network_rx: 110,
+                network_tx: 220,
+                block_read: 6,
+                block_write: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_without_previous_sample() {
+        let stats = ContainerStats::from_response(&ContainerStatsResponse {
+            cpu_stats: cpu(1_500, 10_000),
+            ..Default::default()
+        });
+        assert_eq!(stats.cpu_percent, 0.0);
+        assert_eq!(stats.memory_usage, 0);
+    }
+}
@@ -0,0 +1,825 @@
+//! Tracks whether the server is up and serialises the operations that change that.
+//!
+//! The container being up does not mean players can join, so the server only counts as
+//! [`ServerLifecycle::Running`] once it logs `Done (x.xxxs)!`.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serenity::all::Http;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::{
+    active_features::{alerts, presence},
+    console, docker,
+    log_parser::ServerEvent,
+    server_state::ServerState,
+};
+
+/// Time the server gets to save the world before docker kills it
+const STOP_TIMEOUT_SECS: i32 = 60;
+/// Remaining times at which players are warned about a scheduled restart
+const COUNTDOWN_WARNINGS: [Duration; 3] = [
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(60),
+    Duration::from_secs(10),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerLifecycle {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    Crashed,
+}
+
+impl fmt::Display for ServerLifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ServerLifecycle::Stopped => "stopped",
+            ServerLifecycle::Starting => "starting",
+            ServerLifecycle::Running => "running",
+            ServerLifecycle::Stopping => "stopping",
+            ServerLifecycle::Crashed => "crashed",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Start,
+    Stop,
+    Restart,
+    Kill,
+    Restore,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Start => "start",
+            Operation::Stop => "stop",
+            Operation::Restart => "restart",
+            Operation::Kill => "kill",
+            Operation::Restore => "restore",
+        })
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LifecycleError {
+    #[error("Cannot {operation} the server while it is {state}")]
+    InvalidState {
+        operation: Operation,
+        state: ServerLifecycle,
+    },
+    #[error("A {0} is already in progress")]
+    Busy(Operation),
+    #[error("Docker error: {0}")]
+    Docker(String),
+    #[error("A restart is already scheduled for <t:{}:T>", .0.timestamp())]
+    AlreadyScheduled(DateTime<Utc>),
+}
+
+/// A restart that is counting down, which `/restart cancel` can still stop.
+#[derive(Debug)]
+pub struct ScheduledRestart {
+    at: DateTime<Utc>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+pub struct Lifecycle {
+    state: ServerLifecycle,
+    /// The operation in progress and the state to fall back to if it fails
+    operation: Option<(Operation, ServerLifecycle)>,
+    /// The state to fall back to if a kill fails, kept apart because kills can interrupt an
+    /// operation
+    killed_from: Option<ServerLifecycle>,
+    /// Set while a backup is restored, which stops and starts the server itself
+    restoring: bool,
+}
+
+impl Lifecycle {
+    pub fn new(state: ServerLifecycle) -> Self {
+        Self {
+            state,
+            operation: None,
+            killed_from: None,
+            restoring: false,
+        }
+    }
+
+    pub fn state(&self) -> ServerLifecycle {
+        self.state
+    }
+
+    /// Whether the bot is taking the container down right now, through a stop, kill or restart.
+    ///
+    /// The state alone does not say, a crashing server logs that it is stopping too.
+    pub fn bot_stopping(&self) -> bool {
+        self.killed_from.is_some()
+            || matches!(
+                self.operation,
+                Some((Operation::Stop | Operation::Restart, _))
+            )
+    }
+
+    /// Claims the lifecycle for `operation`, refusing if it makes no sense in the current state
+    /// or another operation is running.
+    ///
+    /// Killing is the way out of a stop that hangs, so it is allowed during other operations.
+    /// Nothing is allowed during a restore, which does its own stops and starts.
+    pub fn begin(&mut self, operation: Operation) -> Result<(), LifecycleError> {
+        if self.restoring {
+            return Err(LifecycleError::Busy(Operation::Restore));
+        }
+        self.begin_step(operation)
+    }
+
+    fn begin_step(&mut self, operation: Operation) -> Result<(), LifecycleError> {
+        use ServerLifecycle::*;
+
+        if let Some((in_progress, _)) = self.operation
+            && operation != Operation::Kill
+        {
+            return Err(LifecycleError::Busy(in_progress));
+        }
+
+        let next = match (operation, self.state) {
+            (Operation::Start, Stopped | Crashed) => Starting,
+            (Operation::Stop, Running | Starting) => Stopping,
+            (Operation::Restart, Running | Stopped | Crashed) => Starting,
+            (Operation::Kill, Running | Starting | Stopping) => Stopping,
+            (Operation::Restore, _) => return Err(LifecycleError::Busy(Operation::Restore)),
+            (operation, state) => return Err(LifecycleError::InvalidState { operation, state }),
+        };
+
+        if operation == Operation::Kill {
+            self.killed_from.get_or_insert(self.state);
+        } else {
+            self.operation = Some((operation, self.state));
+        }
+        self.state = next;
+        Ok(())
+    }
+
+    /// Claims the lifecycle for a restore, which needs the server down and nothing else touching
+    /// it until the restore is finished.
+    pub fn begin_restore(&mut self) -> Result<(), LifecycleError> {
+        use ServerLifecycle::*;
+
+        if let Some((in_progress, _)) = self.operation {
+            return Err(LifecycleError::Busy(in_progress));
+        }
+        if self.restoring || self.killed_from.is_some() {
+            return Err(LifecycleError::Busy(Operation::Restore));
+        }
+        match self.state {
+            Stopped | Crashed | Running | Starting => {
+                self.restoring = true;
+                Ok(())
+            }
+            state => Err(LifecycleError::InvalidState {
+                operation: Operation::Restore,
+                state,
+            }),
+        }
+    }
+
+    pub fn finish_restore(&mut self) {
+        self.restoring = false;
+    }
+
+    /// Records the outcome of the docker call behind `operation`.
+    pub fn finish(&mut self, operation: Operation, success: bool) {
+        let previous = match self.operation {
+            Some((in_progress, previous)) if in_progress == operation => {
+                self.operation = None;
+                Some(previous)
+            }
+            _ if operation == Operation::Kill => self.killed_from.take(),
+            _ => None,
+        };
+
+        self.state = match (operation, success) {
+            (Operation::Stop | Operation::Kill, true) => ServerLifecycle::Stopped,
+            (Operation::Restore, true) => self.state,
+            // Running only comes from the "Done" log line, which may already have arrived
+            (Operation::Start | Operation::Restart, true) => match self.state {
+                ServerLifecycle::Running => ServerLifecycle::Running,
+                _ => ServerLifecycle::Starting,
+            },
+            (_, false) => previous.unwrap_or(self.state),
+        };
+    }
+
+    pub fn on_event(&mut self, event: &ServerEvent) {
+        self.state = match (event, self.state) {
+            (ServerEvent::ServerStarting { .. }, _) => ServerLifecycle::Starting,
+            (ServerEvent::ServerStarted { .. }, ServerLifecycle::Stopping) => {
+                ServerLifecycle::Stopping
+            }
+            (ServerEvent::ServerStarted { .. }, _) => ServerLifecycle::Running,
+            (ServerEvent::ServerStopping, _) => ServerLifecycle::Stopping,
+            (_, state) => state,
+        };
+    }
+
+    /// The console stream closed, which happens when the container stops for any reason.
+    pub fn on_detached(&mut self) {
+        if self.operation.is_some() {
+            return;
+        }
+
+        self.state = match self.state {
+            ServerLifecycle::Stopping => ServerLifecycle::Stopped,
+            ServerLifecycle::Running | ServerLifecycle::Starting => ServerLifecycle::Crashed,
+            state => state,
+        };
+    }
+}
+
+pub async fn update_from_event(server_state: &ServerState, event: &ServerEvent) {
+    server_state
+        .mutables
+        .write()
+        .await
+        .lifecycle
+        .on_event(event);
+}
+
+pub async fn detached(server_state: &ServerState) {
+    let lifecycle = &mut server_state.mutables.write().await.lifecycle;
+    lifecycle.on_detached();
+    log::info!(
+        "Detached from the console, server is now {}",
+        lifecycle.state()
+    );
+}
+
+pub async fn state(server_state: &ServerState) -> ServerLifecycle {
+    server_state.mutables.read().await.lifecycle.state()
+}
+
+/// Begins `operation`, as a step of the restore holding the lifecycle if there is one.
+async fn begin(
+    server_state: &ServerState,
+    operation: Operation,
+    restore: Option<&Restore<'_>>,
+) -> Result<(), LifecycleError> {
+    let mut mutables = server_state.mutables.write().await;
+    match restore {
+        Some(_) => mutables.lifecycle.begin_step(operation)?,
+        None => mutables.lifecycle.begin(operation)?,
+    }
+    log::info!(
+        "Beginning {operation}, server is now {}",
+        mutables.lifecycle.state()
+    );
+    Ok(())
+}
+
+async fn finish(
+    server_state: &ServerState,
+    operation: Operation,
+    result: Result<(), String>,
+) -> Result<(), LifecycleError> {
+    let mut mutables = server_state.mutables.write().await;
+    mutables.lifecycle.finish(operation, result.is_ok());
+    log::info!(
+        "Finished {operation}, server is now {}",
+        mutables.lifecycle.state()
+    );
+    result.map_err(LifecycleError::Docker)
+}
+
+pub async fn start(server_state: &ServerState) -> Result<(), LifecycleError> {
+    start_as(server_state, None).await
+}
+
+async fn start_as(
+    server_state: &ServerState,
+    restore: Option<&Restore<'_>>,
+) -> Result<(), LifecycleError> {
+    begin(server_state, Operation::Start, restore).await?;
+    let result = docker::start_server(server_state).await;
+    finish(server_state, Operation::Start, result).await
+}
+
+/// Asks the server to save and shut down, then stops the container so its restart policy
+/// does not bring it back.
+pub async fn stop(server_state: &ServerState) -> Result<(), LifecycleError> {
+    stop_as(server_state, None).await
+}
+
+async fn stop_as(
+    server_state: &ServerState,
+    restore: Option<&Restore<'_>>,
+) -> Result<(), LifecycleError> {
+    begin(server_state, Operation::Stop, restore).await?;
+    if let Err(e) = console::send_command(server_state, "stop").await {
+        log::warn!("Could not send stop to the console, relying on docker: {e}");
+    }
+    let result = docker::stop_server(server_state, STOP_TIMEOUT_SECS).await;
+    finish(server_state, Operation::Stop, result).await
+}
+
+pub async fn restart(server_state: &ServerState) -> Result<(), LifecycleError> {
+    begin(server_state, Operation::Restart, None).await?;
+    presence::set_restarting(server_state, true).await;
+    let result = docker::restart_server(server_state).await;
+    presence::set_restarting(server_state, false).await;
+    finish(server_state, Operation::Restart, result).await
+}
+
+pub async fn kill(server_state: &ServerState) -> Result<(), LifecycleError> {
+    kill_as(server_state, None).await
+}
+
+async fn kill_as(
+    server_state: &ServerState,
+    restore: Option<&Restore<'_>>,
+) -> Result<(), LifecycleError> {
+    begin(server_state, Operation::Kill, restore).await?;
+    let result = docker::kill_server(server_state).await;
+    finish(server_state, Operation::Kill, result).await
+}
+
+/// The lifecycle held by a restore, which nothing else can start or stop the server through
+/// until [`Restore::finish`].
+pub struct Restore<'a> {
+    server_state: &'a ServerState,
+}
+
+pub async fn begin_restore(server_state: &ServerState) -> Result<Restore<'_>, LifecycleError> {
+    let mut mutables = server_state.mutables.write().await;
+    mutables.lifecycle.begin_restore()?;
+    log::info!(
+        "Beginning restore, server is {}",
+        mutables.lifecycle.state()
+    );
+    Ok(Restore { server_state })
+}
+
+impl Restore<'_> {
+    pub async fn start(&self) -> Result<(), LifecycleError> {
+        start_as(self.server_state, Some(self)).await
+    }
+
+    pub async fn stop(&self) -> Result<(), LifecycleError> {
+        stop_as(self.server_state, Some(self)).await
+    }
+
+    pub async fn kill(&self) -> Result<(), LifecycleError> {
+        kill_as(self.server_state, Some(self)).await
+    }
+
+    pub async fn finish(self) {
+        let mut mutables = self.server_state.mutables.write().await;
+        mutables.lifecycle.finish_restore();
+        log::info!(
+            "Finished restore, server is now {}",
+            mutables.lifecycle.state()
+        );
+    }
+}
+
+/// Remaining times to warn players at, starting with the whole delay.
+fn countdown(delay: Duration) -> Vec<Duration> {
+    let mut warnings = vec![delay];
+    warnings.extend(
+        COUNTDOWN_WARNINGS
+            .into_iter()
+            .filter(|warning| *warning < delay),
+    );
+    warnings
+}
+
+fn describe_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    match (secs / 60, secs % 60) {
+        (1, 0) => "1 minute".to_string(),
+        (minutes, 0) if minutes > 0 => format!("{minutes} minutes"),
+        _ => format!("{secs} seconds"),
+    }
+}
+
+async fn warn_players(server_state: &ServerState, message: &str) {
+    if let Err(e) = console::send_command(server_state, &format!("say {message}")).await {
+        log::warn!("Could not warn players about the restart: {e}");
+    }
+}
+
+/// Restarts the server after `delay`, counting down in game.
+pub async fn schedule_restart(
+    server_state: &Arc<ServerState>,
+    http: Arc<Http>,
+    delay: Duration,
+) -> Result<DateTime<Utc>, LifecycleError> {
+    let mut mutables = server_state.mutables.write().await;
+    if let Some(scheduled) = &mutables.scheduled_restart {
+        return Err(LifecycleError::AlreadyScheduled(scheduled.at));
+    }
+
+    let at = Utc::now() + delay;
+    let task = tokio::task::spawn({
+        let server_state = server_state.clone();
+        async move {
+            let start = tokio::time::Instant::now();
+            for remaining in countdown(delay) {
+                tokio::time::sleep_until(start + (delay - remaining)).await;
+                warn_players(
+                    &server_state,
+                    &format!("Server restarting in {}", describe_remaining(remaining)),
+                )
+                .await;
+            }
+            tokio::time::sleep_until(start + delay).await;
+
+            // Past the point of cancelling
+            server_state.mutables.write().await.scheduled_restart = None;
+            log::info!("Running scheduled restart");
+            if let Err(e) = restart(&server_state).await {
+                let _ = alerts::alert(
+                    &server_state,
+                    &http,
+                    format!(":x: Scheduled restart failed: {e}"),
+                )
+                .await
+                .map_err(|e| log::error!("Error in alert: {e}"));
+            }
+        }
+    });
+    mutables.scheduled_restart = Some(ScheduledRestart { at, task });
+
+    Ok(at)
+}
+
+/// Stops a scheduled restart, returning when it would have happened.
+pub async fn cancel_scheduled_restart(server_state: &ServerState) -> Option<DateTime<Utc>> {
+    let scheduled = server_state
+        .mutables
+        .write()
+        .await
+        .scheduled_restart
+        .take()?;
+    scheduled.task.abort();
+    warn_players(server_state, "Restart cancelled").await;
+    Some(scheduled.at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LifecycleError, Operation, ServerLifecycle::*, *};
+
+    fn started() -> ServerEvent {
+        ServerEvent::ServerStarted { startup_time: None }
+    }
+
+    /// The result of beginning `operation` from `state`, and the state after it.
+    fn begun(
+        operation: Operation,
+        state: ServerLifecycle,
+    ) -> (Result<(), LifecycleError>, ServerLifecycle) {
+        let mut lifecycle = Lifecycle::new(state);
+        let result = lifecycle.begin(operation);
+        (result, lifecycle.state())
+    }
+
+    fn refused(
+        operation: Operation,
+        state: ServerLifecycle,
+    ) -> (Result<(), LifecycleError>, ServerLifecycle) {
+        (
+            Err(LifecycleError::InvalidState { operation, state }),
+            state,
+        )
+    }
+
+    #[test]
+    fn begin_transitions() {
+        // Start from a stopped or crashed server
+        assert_eq!(begun(Operation::Start, Stopped), (Ok(()), Starting));
+        assert_eq!(begun(Operation::Start, Crashed), (Ok(()), Starting));
+
+        // Start when it is already up or on its way
+        assert_eq!(
+            begun(Operation::Start, Running),
+            refused(Operation::Start, Running)
+        );
+        assert_eq!(
+            begun(Operation::Start, Starting),
+            refused(Operation::Start, Starting)
+        );
+        assert_eq!(
+            begun(Operation::Start, Stopping),
+            refused(Operation::Start, Stopping)
+        );
+
+        // Stop a running or starting server
+        assert_eq!(begun(Operation::Stop, Running), (Ok(()), Stopping));
+        assert_eq!(begun(Operation::Stop, Starting), (Ok(()), Stopping));
+
+        // Stop when it is already down or on its way
+        assert_eq!(
+            begun(Operation::Stop, Stopped),
+            refused(Operation::Stop, Stopped)
+        );
+        assert_eq!(
+            begun(Operation::Stop, Crashed),
+            refused(Operation::Stop, Crashed)
+        );
+        assert_eq!(
+            begun(Operation::Stop, Stopping),
+            refused(Operation::Stop, Stopping)
+        );
+
+        // Restart works whether or not the server is up
+        assert_eq!(begun(Operation::Restart, Running), (Ok(()), Starting));
+        assert_eq!(begun(Operation::Restart, Stopped), (Ok(()), Starting));
+        assert_eq!(begun(Operation::Restart, Crashed), (Ok(()), Starting));
+
+        // Restart in the middle of starting or stopping
+        assert_eq!(
+            begun(Operation::Restart, Starting),
+            refused(Operation::Restart, Starting)
+        );
+        assert_eq!(
+            begun(Operation::Restart, Stopping),
+            refused(Operation::Restart, Stopping)
+        );
+
+        // Kill, even while a stop is under way
+        assert_eq!(begun(Operation::Kill, Running), (Ok(()), Stopping));
+        assert_eq!(begun(Operation::Kill, Starting), (Ok(()), Stopping));
+        assert_eq!(begun(Operation::Kill, Stopping), (Ok(()), Stopping));
+
+        // Kill when there is nothing to kill
+        assert_eq!(
+            begun(Operation::Kill, Stopped),
+            refused(Operation::Kill, Stopped)
+        );
+        assert_eq!(
+            begun(Operation::Kill, Crashed),
+            refused(Operation::Kill, Crashed)
+        );
+    }
+
+    #[test]
+    fn refuses_overlapping_operations() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Restart).unwrap();
+
+        assert_eq!(
+            lifecycle.begin(Operation::Stop),
+            Err(LifecycleError::Busy(Operation::Restart))
+        );
+        assert_eq!(
+            lifecycle.begin(Operation::Restart),
+            Err(LifecycleError::Busy(Operation::Restart))
+        );
+
+        lifecycle.finish(Operation::Restart, true);
+        assert_eq!(lifecycle.begin(Operation::Stop), Ok(()));
+    }
+
+    #[test]
+    fn restores_hold_the_lifecycle() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin_restore().unwrap();
+
+        // Nobody else can touch the server, not even to kill it
+        assert_eq!(
+            lifecycle.begin(Operation::Start),
+            Err(LifecycleError::Busy(Operation::Restore))
+        );
+        assert_eq!(
+            lifecycle.begin(Operation::Kill),
+            Err(LifecycleError::Busy(Operation::Restore))
+        );
+        assert_eq!(
+            lifecycle.begin_restore(),
+            Err(LifecycleError::Busy(Operation::Restore))
+        );
+
+        // The restore stops and starts it itself
+        lifecycle.begin_step(Operation::Stop).unwrap();
+        lifecycle.finish(Operation::Stop, true);
+        assert_eq!(lifecycle.state(), Stopped);
+        lifecycle.begin_step(Operation::Start).unwrap();
+        lifecycle.finish(Operation::Start, true);
+        assert_eq!(lifecycle.state(), Starting);
+
+        lifecycle.finish_restore();
+        assert_eq!(lifecycle.begin(Operation::Stop), Ok(()));
+    }
+
+    #[test]
+    fn restores_need_a_settled_server() {
+        // Stopped, crashed, running or starting are fine
+        assert_eq!(Lifecycle::new(Stopped).begin_restore(), Ok(()));
+        assert_eq!(Lifecycle::new(Crashed).begin_restore(), Ok(()));
+        assert_eq!(Lifecycle::new(Running).begin_restore(), Ok(()));
+        assert_eq!(Lifecycle::new(Starting).begin_restore(), Ok(()));
+
+        // The server may still be writing the world while it stops
+        assert_eq!(
+            Lifecycle::new(Stopping).begin_restore(),
+            Err(LifecycleError::InvalidState {
+                operation: Operation::Restore,
+                state: Stopping
+            })
+        );
+
+        // In the middle of a restart
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Restart).unwrap();
+        assert_eq!(
+            lifecycle.begin_restore(),
+            Err(LifecycleError::Busy(Operation::Restart))
+        );
+    }
+
+    #[test]
+    fn kill_interrupts_a_stop() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Stop).unwrap();
+        lifecycle.begin(Operation::Kill).unwrap();
+        lifecycle.finish(Operation::Kill, true);
+        assert_eq!(lifecycle.state(), Stopped);
+
+        // The stop returns once the container is gone
+        lifecycle.finish(Operation::Stop, true);
+        assert_eq!(lifecycle.state(), Stopped);
+        assert_eq!(lifecycle.begin(Operation::Start), Ok(()));
+    }
+
+    #[test]
+    fn failed_kill_rolls_back() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Kill).unwrap();
+        lifecycle.finish(Operation::Kill, false);
+        assert_eq!(lifecycle.state(), Running);
+        assert_eq!(lifecycle.begin(Operation::Stop), Ok(()));
+
+        let mut lifecycle = Lifecycle::new(Starting);
+        lifecycle.begin(Operation::Kill).unwrap();
+        lifecycle.finish(Operation::Kill, false);
+        assert_eq!(lifecycle.state(), Starting);
+
+        // The stop it tried to cut short is still going
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Stop).unwrap();
+        lifecycle.begin(Operation::Kill).unwrap();
+        lifecycle.finish(Operation::Kill, false);
+        assert_eq!(lifecycle.state(), Stopping);
+        lifecycle.finish(Operation::Stop, false);
+        assert_eq!(lifecycle.state(), Running);
+    }
+
+    #[test]
+    fn running_only_after_done() {
+        let mut lifecycle = Lifecycle::new(Stopped);
+        lifecycle.begin(Operation::Start).unwrap();
+        lifecycle.finish(Operation::Start, true);
+        assert_eq!(lifecycle.state(), Starting);
+
+        lifecycle.on_event(&ServerEvent::ServerStarting {
+            version: "1.21.10".to_string(),
+        });
+        assert_eq!(lifecycle.state(), Starting);
+
+        lifecycle.on_event(&started());
+        assert_eq!(lifecycle.state(), Running);
+    }
+
+    #[test]
+    fn done_before_restart_returns() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Restart).unwrap();
+        lifecycle.on_event(&started());
+        lifecycle.finish(Operation::Restart, true);
+        assert_eq!(lifecycle.state(), Running);
+    }
+
+    #[test]
+    fn failed_operations_roll_back() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Stop).unwrap();
+        lifecycle.finish(Operation::Stop, false);
+        assert_eq!(lifecycle.state(), Running);
+
+        let mut lifecycle = Lifecycle::new(Crashed);
+        lifecycle.begin(Operation::Start).unwrap();
+        lifecycle.finish(Operation::Start, false);
+        assert_eq!(lifecycle.state(), Crashed);
+        assert_eq!(lifecycle.begin(Operation::Start), Ok(()));
+    }
+
+    #[test]
+    fn stop_from_console() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.on_event(&ServerEvent::ServerStopping);
+        assert_eq!(lifecycle.state(), Stopping);
+
+        lifecycle.on_detached();
+        assert_eq!(lifecycle.state(), Stopped);
+    }
+
+    #[test]
+    fn crash_detection() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.on_detached();
+        assert_eq!(lifecycle.state(), Crashed);
+
+        let mut lifecycle = Lifecycle::new(Starting);
+        lifecycle.on_detached();
+        assert_eq!(lifecycle.state(), Crashed);
+
+        // The stream closing is expected while an operation is running
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Restart).unwrap();
+        lifecycle.on_detached();
+        assert_eq!(lifecycle.state(), Starting);
+    }
+
+    #[test]
+    fn bot_stops() {
+        let mut lifecycle = Lifecycle::new(Running);
+        assert!(!lifecycle.bot_stopping());
+
+        // A crash logs this as well
+        lifecycle.on_event(&ServerEvent::ServerStopping);
+        assert!(!lifecycle.bot_stopping());
+
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Restart).unwrap();
+        assert!(lifecycle.bot_stopping());
+        lifecycle.finish(Operation::Restart, true);
+        assert!(!lifecycle.bot_stopping());
+
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Stop).unwrap();
+        assert!(lifecycle.bot_stopping());
+
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.begin(Operation::Kill).unwrap();
+        assert!(lifecycle.bot_stopping());
+        lifecycle.finish(Operation::Kill, true);
+        assert!(!lifecycle.bot_stopping());
+
+        // Starting does not take the container down
+        let mut lifecycle = Lifecycle::new(Stopped);
+        lifecycle.begin(Operation::Start).unwrap();
+        assert!(!lifecycle.bot_stopping());
+    }
+
+    #[test]
+    fn countdowns() {
+        let secs = |delay: Duration| -> Vec<_> {
+            countdown(delay).iter().map(Duration::as_secs).collect()
+        };
+
+        // Ten minutes warns at every step
+        assert_eq!(secs(Duration::from_secs(600)), vec![600, 300, 60, 10]);
+
+        // Five minutes
+        assert_eq!(secs(Duration::from_secs(300)), vec![300, 60, 10]);
+
+        // Between steps, the first warning is the full delay
+        assert_eq!(secs(Duration::from_secs(180)), vec![180, 60, 10]);
+
+        // One minute
+        assert_eq!(secs(Duration::from_secs(60)), vec![60, 10]);
+
+        // Shorter than the last step
+        assert_eq!(secs(Duration::from_secs(5)), vec![5]);
+    }
+
+    #[test]
+    fn remaining_descriptions() {
+        // Whole minutes
+        assert_eq!(describe_remaining(Duration::from_secs(300)), "5 minutes");
+
+        // A single minute
+        assert_eq!(describe_remaining(Duration::from_secs(60)), "1 minute");
+
+        // Under a minute
+        assert_eq!(describe_remaining(Duration::from_secs(10)), "10 seconds");
+
+        // Not a whole number of minutes
+        assert_eq!(describe_remaining(Duration::from_secs(90)), "90 seconds");
+    }
+
+    #[test]
+    fn other_events_do_not_change_state() {
+        let mut lifecycle = Lifecycle::new(Running);
+        lifecycle.on_event(&ServerEvent::Join {
+            player: "sally".to_string(),
+        });
+        assert_eq!(lifecycle.state(), Running);
+    }
+}
@@ -0,0 +1,1037 @@
+//! Turns lines from the server console into typed [`ServerEvent`]s.
+//!
+//! Understands the vanilla / Spigot layout (`[20:41:25] [Server thread/INFO]: ...`), the
+//! Forge variant with a logger name (`[20:41:25] [Server thread/INFO] [minecraft/Server]: ...`)
+//! and the Paper layout (`[20:41:25 INFO]: ...`).
+
+use std::{str::FromStr, time::Duration};
+
+use chrono::NaiveTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "TRACE" => Ok(Self::Trace),
+            "DEBUG" => Ok(Self::Debug),
+            "INFO" => Ok(Self::Info),
+            "WARN" | "WARNING" => Ok(Self::Warn),
+            // Older Spigot builds log through java.util.logging
+            "ERROR" | "SEVERE" => Ok(Self::Error),
+            "FATAL" => Ok(Self::Fatal),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeaveReason {
+    /// "sally left the game"
+    Left,
+    /// "sally lost connection: Timed out"
+    LostConnection(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvancementKind {
+    Advancement,
+    Goal,
+    Challenge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    Join {
+        player: String,
+    },
+    Leave {
+        player: String,
+        reason: LeaveReason,
+    },
+    Chat {
+        player: String,
+        message: String,
+    },
+    /// `message` is the full death message, including the player name.
+    Death {
+        player: String,
+        message: String,
+    },
+    Advancement {
+        player: String,
+        kind: AdvancementKind,
+        advancement: String,
+    },
+    ServerStarting {
+        version: String,
+    },
+    /// The server logged `Done (x.xxxs)!` and is accepting players.
+    ServerStarted {
+        startup_time: Option<Duration>,
+    },
+    ServerStopping,
+    /// `Can't keep up!`, the server fell `behind` by that much time or that many `ticks`.
+    Lag {
+        behind: Duration,
+        ticks: u64,
+    },
+    Warning {
+        message: String,
+    },
+    /// Feedback from a console command, e.g. `list` or `whitelist add`.
+    CommandOutput {
+        message: String,
+    },
+    Unknown {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub timestamp: Option<NaiveTime>,
+    pub level: Option<LogLevel>,
+    pub thread: Option<String>,
+    pub event: ServerEvent,
+}
+
+pub fn parse(line: &str) -> LogLine {
+    let Some((header, message)) = split_header(line) else {
+        // Continuation lines, stack traces and container init output have no header
+        return LogLine {
+            timestamp: None,
+            level: None,
+            thread: None,
+            event: ServerEvent::Unknown {
+                message: line.to_string(),
+            },
+        };
+    };
+
+    LogLine {
+        timestamp: Some(header.timestamp),
+        level: header.level,
+        thread: header.thread.map(str::to_string),
+        event: parse_event(message, header.level),
+    }
+}
+
+struct Header<'a> {
+    timestamp: NaiveTime,
+    level: Option<LogLevel>,
+    thread: Option<&'a str>,
+}
+
+fn split_header(line: &str) -> Option<(Header<'_>, &str)> {
+    let (first, rest) = line.strip_prefix('[')?.split_once(']')?;
+
+    // Paper: "[20:41:25 INFO]: message"
+    if let Some((time, level)) = first.split_once(' ') {
+        let header = Header {
+            timestamp: parse_time(time)?,
+            level: level.parse().ok(),
+            thread: None,
+        };
+        return Some((header, strip_separator(rest)?));
+    }
+
+    // Vanilla / Spigot: "[20:41:25] [Server thread/INFO]: message"
+    let timestamp = parse_time(first)?;
+    let (thread_level, rest) = rest.strip_prefix(" [")?.split_once(']')?;
+    let (thread, level) = thread_level.rsplit_once('/')?;
+
+    // Forge: "[20:41:25] [Server thread/INFO] [minecraft/DedicatedServer]: message"
+    let rest = match rest.strip_prefix(" [") {
+        Some(logger) => logger.split_once(']')?.1,
+        None => rest,
+    };
+
+    let header = Header {
+        timestamp,
+        level: level.parse().ok(),
+        thread: Some(thread),
+    };
+    Some((header, strip_separator(rest)?))
+}
+
+fn strip_separator(s: &str) -> Option<&str> {
+    let s = s.strip_prefix(':')?;
+    Some(s.strip_prefix(' ').unwrap_or(s))
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S%.f").ok()
+}
+
+fn parse_event(message: &str, level: Option<LogLevel>) -> ServerEvent {
+    if let Some(event) = parse_chat(message) {
+        return event;
+    }
+
+    if let Some(event) = parse_lag(message) {
+        return event;
+    }
+
+    if level == Some(LogLevel::Warn) {
+        return ServerEvent::Warning {
+            message: message.to_string(),
+        };
+    }
+
+    parse_presence(message)
+        .or_else(|| parse_advancement(message))
+        .or_else(|| parse_lifecycle(message))
+        .or_else(|| parse_death(message))
+        .or_else(|| parse_command_output(message))
+        .unwrap_or_else(|| ServerEvent::Unknown {
+            message: message.to_string(),
+        })
+}
+
+/// Minecraft Java account names: 3 to 16 letters, digits or underscores.
+pub fn is_valid_player_name(name: &str) -> bool {
+    (3..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_chat(message: &str) -> Option<ServerEvent> {
+    // Servers with chat signing disabled prefix unsigned messages
+    let message = message.strip_prefix("[Not Secure] ").unwrap_or(message);
+    let (player, message) = message.strip_prefix('<')?.split_once("> ")?;
+
+    Some(ServerEvent::Chat {
+        player: player.to_string(),
+        message: message.to_string(),
+    })
+}
+
+fn parse_presence(message: &str) -> Option<ServerEvent> {
+    if let Some(player) = message.strip_suffix(" joined the game") {
+        // "sally (formerly known as bob) joined the game"
+        let player = player
+            .split_once(" (formerly known as ")
+            .map_or(player, |(player, _)| player);
+        // Plugins and `/say` can log the same words, like "[Server] sally joined the game"
+        return is_valid_player_name(player).then(|| ServerEvent::Join {
+            player: player.to_string(),
+        });
+    }
+
+    if let Some(player) = message.strip_suffix(" left the game") {
+        if !is_valid_player_name(player) {
+            return None;
+        }
+        return Some(ServerEvent::Leave {
+            player: player.to_string(),
+            reason: LeaveReason::Left,
+        });
+    }
+
+    let (player, reason) = message.split_once(" lost connection: ")?;
+    // Paper appends the address: "sally (/127.0.0.1:5555) lost connection: ..."
+    let player = player.split_once(" (").map_or(player, |(player, _)| player);
+    // Connections that never logged in report an address or a GameProfile instead
+    if !is_valid_player_name(player) {
+        return None;
+    }
+
+    Some(ServerEvent::Leave {
+        player: player.to_string(),
+        reason: LeaveReason::LostConnection(reason.to_string()),
+    })
+}
+
+fn parse_advancement(message: &str) -> Option<ServerEvent> {
+    const KINDS: [(&str, AdvancementKind); 3] = [
+        (" has made the advancement [", AdvancementKind::Advancement),
+        (" has reached the goal [", AdvancementKind::Goal),
+        (" has completed the challenge [", AdvancementKind::Challenge),
+    ];
+
+    KINDS.iter().find_map(|(pattern, kind)| {
+        let (player, advancement) = message.split_once(pattern)?;
+        Some(ServerEvent::Advancement {
+            player: player.to_string(),
+            kind: *kind,
+            advancement: advancement.strip_suffix(']')?.to_string(),
+        })
+    })
+}
+
+fn parse_lifecycle(message: &str) -> Option<ServerEvent> {
+    if let Some(version) = message.strip_prefix("Starting minecraft server version ") {
+        return Some(ServerEvent::ServerStarting {
+            version: version.to_string(),
+        });
+    }
+
+    // "Done (3.456s)! For help, type "help""
+    if let Some(rest) = message.strip_prefix("Done (") {
+        let (seconds, _) = rest.split_once(")!")?;
+        let startup_time = seconds
+            .strip_suffix('s')
+            .and_then(|s| s.parse::<f64>().ok())
+            .and_then(|s| Duration::try_from_secs_f64(s).ok());
+        return Some(ServerEvent::ServerStarted { startup_time });
+    }
+
+    if message == "Stopping the server" || message == "Stopping server" {
+        return Some(ServerEvent::ServerStopping);
+    }
+
+    None
+}
+
+fn parse_lag(message: &str) -> Option<ServerEvent> {
+    let rest = message.strip_prefix("Can't keep up! ")?;
+    let (_, rest) = rest.split_once("Running ")?;
+    let (millis, rest) = rest.split_once("ms")?;
+    let ticks = rest
+        // "Running 2500ms or 50 ticks behind"
+        .strip_prefix(" or ")
+        .and_then(|rest| rest.strip_suffix(" ticks behind"))
+        // Older versions: "Running 2500ms behind, skipping 50 tick(s)"
+        .or_else(|| {
+            rest.strip_prefix(" behind, skipping ")
+                .and_then(|rest| rest.strip_suffix(" tick(s)"))
+        })?;
+
+    Some(ServerEvent::Lag {
+        behind: Duration::from_millis(millis.parse().ok()?),
+        ticks: ticks.parse().ok()?,
+    })
+}
+
+fn parse_death(message: &str) -> Option<ServerEvent> {
+    // Death messages are "<player> <cause>", taken from the vanilla language file
+    const CAUSES: &[&str] = &[
+        "was slain by ",
+        "was shot by ",
+        "was pummeled by ",
+        "was fireballed by ",
+        "was killed",
+        "was blown up by ",
+        "was impaled",
+        "was squashed",
+        "was squished",
+        "was skewered",
+        "was stung to death",
+        "was poked to death",
+        "was pricked to death",
+        "was struck by lightning",
+        "was frozen to death",
+        "was roasted in dragon",
+        "was doomed to fall",
+        "was burned to a crisp",
+        "was burnt to a crisp",
+        "was obliterated",
+        "was smashed by ",
+        "was speared by ",
+        "was sniped by ",
+        "was spitballed by ",
+        "was too soft for this world",
+        "blew up",
+        "drowned",
+        "died",
+        "experienced kinetic energy",
+        "fell ",
+        "hit the ground too hard",
+        "burned to death",
+        "went up in flames",
+        "went off with a bang",
+        "walked into ",
+        "tried to swim in lava",
+        "discovered the floor was lava",
+        "starved to death",
+        "suffocated in a wall",
+        "left the confines of this world",
+        "withered away",
+        "froze to death",
+        "didn't want to live",
+    ];
+
+    let (player, cause) = message.split_once(' ')?;
+    if !is_valid_player_name(player) || !CAUSES.iter().any(|c| cause.starts_with(c)) {
+        return None;
+    }
+
+    Some(ServerEvent::Death {
+        player: player.to_string(),
+        message: message.to_string(),
+    })
+}
+
+fn parse_command_output(message: &str) -> Option<ServerEvent> {
+    const PREFIXES: &[&str] = &[
+        // say
+        "[Server] ",
+        // list
+        "There are ",
+        // whitelist
+        "Added ",
+        "Removed ",
+        "Player is already whitelisted",
+        "Player is not whitelisted",
+        "Whitelist is ",
+        "Reloaded the whitelist",
+        // save-all / save-off / save-on
+        "Saving the game",
+        "Saved the game",
+        "Automatic saving is now",
+        "Saving is already turned",
+        // op / kick / ban
+        "Made ",
+        "Nothing changed",
+        "Kicked ",
+        "Banned ",
+        "Unbanned ",
+        // errors
+        "Unknown or incomplete command",
+        "Incorrect argument for command",
+    ];
+
+    // Feedback broadcast to operators: "[Rcon: Saved the game]"
+    let is_feedback = message
+        .strip_prefix('[')
+        .and_then(|m| m.strip_suffix(']'))
+        .and_then(|m| m.split_once(": "))
+        .is_some_and(|(source, _)| !source.contains(']'));
+
+    if !is_feedback && !PREFIXES.iter().any(|p| message.starts_with(p)) {
+        return None;
+    }
+
+    Some(ServerEvent::CommandOutput {
+        message: message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> ServerEvent {
+        parse(line).event
+    }
+
+    fn join(player: &str) -> ServerEvent {
+        ServerEvent::Join {
+            player: player.to_string(),
+        }
+    }
+
+    fn chat(player: &str, message: &str) -> ServerEvent {
+        ServerEvent::Chat {
+            player: player.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn unknown(message: &str) -> ServerEvent {
+        ServerEvent::Unknown {
+            message: message.to_string(),
+        }
+    }
+
+    fn leave(player: &str, reason: LeaveReason) -> ServerEvent {
+        ServerEvent::Leave {
+            player: player.to_string(),
+            reason,
+        }
+    }
+
+    fn lost(reason: &str) -> LeaveReason {
+        LeaveReason::LostConnection(reason.to_string())
+    }
+
+    fn death(player: &str, message: &str) -> ServerEvent {
+        ServerEvent::Death {
+            player: player.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn advancement(player: &str, kind: AdvancementKind, advancement: &str) -> ServerEvent {
+        ServerEvent::Advancement {
+            player: player.to_string(),
+            kind,
+            advancement: advancement.to_string(),
+        }
+    }
+
+    fn warning(message: &str) -> ServerEvent {
+        ServerEvent::Warning {
+            message: message.to_string(),
+        }
+    }
+
+    fn output(message: &str) -> ServerEvent {
+        ServerEvent::CommandOutput {
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn headers() {
+        // Paper
+        let parsed = parse("[20:41:25 INFO]: sally joined the game");
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(20, 41, 25));
+        assert_eq!(parsed.level, Some(LogLevel::Info));
+        assert_eq!(parsed.thread, None);
+
+        // Paper warning
+        let parsed = parse("[09:01:02 WARN]: Can't keep up!");
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(9, 1, 2));
+        assert_eq!(parsed.level, Some(LogLevel::Warn));
+
+        // Vanilla / Spigot
+        let parsed = parse("[20:41:25] [Server thread/INFO]: sally joined the game");
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(20, 41, 25));
+        assert_eq!(parsed.level, Some(LogLevel::Info));
+        assert_eq!(parsed.thread.as_deref(), Some("Server thread"));
+
+        // Thread name with spaces and a number
+        let parsed = parse("[20:41:25] [User Authenticator #1/INFO]: UUID of player sally is 1234");
+        assert_eq!(parsed.thread.as_deref(), Some("User Authenticator #1"));
+
+        // Error
+        let parsed = parse("[00:00:00] [Server thread/ERROR]: Encountered an unexpected exception");
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(0, 0, 0));
+        assert_eq!(parsed.level, Some(LogLevel::Error));
+
+        // SEVERE is an error too
+        let parsed = parse("[00:00:00] [Server thread/SEVERE]: Could not load plugin");
+        assert_eq!(parsed.level, Some(LogLevel::Error));
+
+        // Forge
+        let parsed = parse(
+            "[20:41:25] [Server thread/INFO] [minecraft/DedicatedServer]: sally joined the game",
+        );
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(20, 41, 25));
+        assert_eq!(parsed.level, Some(LogLevel::Info));
+        assert_eq!(parsed.thread.as_deref(), Some("Server thread"));
+
+        // Unrecognised level
+        let parsed = parse("[20:41:25 NOTICE]: something");
+        assert_eq!(parsed.timestamp, NaiveTime::from_hms_opt(20, 41, 25));
+        assert_eq!(parsed.level, None);
+        assert_eq!(parsed.thread, None);
+    }
+
+    #[test]
+    fn fractional_timestamp() {
+        let parsed = parse("[20:41:25.123] [Server thread/INFO]: hi");
+        assert_eq!(
+            parsed.timestamp,
+            NaiveTime::from_hms_milli_opt(20, 41, 25, 123)
+        );
+    }
+
+    #[test]
+    fn no_header() {
+        // Plain text
+        let s = "sally joined the game";
+        assert_eq!(parse(s).timestamp, None);
+        assert_eq!(parse(s).level, None);
+        assert_eq!(event(s), unknown(s));
+
+        // Container startup script
+        let s = "[init] Running as uid=1000 gid=1000";
+        assert_eq!(parse(s).level, None);
+        assert_eq!(event(s), unknown(s));
+
+        // Container process runner
+        let s = "[mc-server-runner] Waiting for process to finish";
+        assert_eq!(parse(s).level, None);
+        assert_eq!(event(s), unknown(s));
+
+        // Stack trace
+        let s = "\tat net.minecraft.server.MinecraftServer.run(MinecraftServer.java:1)";
+        assert_eq!(parse(s).level, None);
+        assert_eq!(event(s), unknown(s));
+
+        // Empty line
+        let s = "";
+        assert_eq!(parse(s).timestamp, None);
+        assert_eq!(event(s), unknown(s));
+
+        // Header without the colon
+        let s = "[20:41:25 INFO] missing colon";
+        assert_eq!(parse(s).timestamp, None);
+        assert_eq!(event(s), unknown(s));
+    }
+
+    #[test]
+    fn joins() {
+        // Normal name
+        let s = "[20:41:25 INFO]: sally joined the game";
+        assert_eq!(event(s), join("sally"));
+
+        // normal with caps
+        let s = "[20:41:25 INFO]: Sally joined the game";
+        assert_eq!(event(s), join("Sally"));
+
+        // Vanilla / Spigot
+        let s = "[20:41:25] [Server thread/INFO]: sally joined the game";
+        assert_eq!(event(s), join("sally"));
+
+        // Renamed since last login
+        let s = "[20:41:25] [Server thread/INFO]: sally (formerly known as bob) joined the game";
+        assert_eq!(event(s), join("sally"));
+    }
+
+    #[test]
+    fn joins_need_a_player_name() {
+        // Multi word name
+        let s = "[20:41:25 INFO]: Sally Whiller joined the game";
+        assert_eq!(event(s), unknown("Sally Whiller joined the game"));
+
+        // Single character name, too short for an account
+        let s = "[20:41:25 INFO]: a joined the game";
+        assert_eq!(event(s), unknown("a joined the game"));
+
+        // just a space
+        let s = "[20:41:25 INFO]:   joined the game";
+        assert_eq!(event(s), unknown("  joined the game"));
+
+        // /say from the console
+        let s = "[20:41:25 INFO]: [Server] x joined the game";
+        assert_eq!(
+            event(s),
+            ServerEvent::CommandOutput {
+                message: "[Server] x joined the game".to_string()
+            }
+        );
+
+        // The same goes for leaving
+        let s = "[20:41:25 INFO]: Sally Whiller left the game";
+        assert_eq!(event(s), unknown("Sally Whiller left the game"));
+    }
+
+    #[test]
+    fn leaves() {
+        // Paper
+        let s = "[20:41:25 INFO]: sally left the game";
+        assert_eq!(event(s), leave("sally", LeaveReason::Left));
+
+        // Vanilla / Spigot
+        let s = "[20:41:25] [Server thread/INFO]: Sally_2 left the game";
+        assert_eq!(event(s), leave("Sally_2", LeaveReason::Left));
+
+        // Lost connection
+        let s = "[20:41:25] [Server thread/INFO]: sally lost connection: Disconnected";
+        assert_eq!(event(s), leave("sally", lost("Disconnected")));
+
+        // Timed out
+        let s = "[20:41:25 INFO]: sally lost connection: Timed out";
+        assert_eq!(event(s), leave("sally", lost("Timed out")));
+
+        // Kicked, with the address logged
+        let s = "[20:41:25 INFO]: sally (/127.0.0.1:5555) lost connection: Kicked by an operator";
+        assert_eq!(event(s), leave("sally", lost("Kicked by an operator")));
+    }
+
+    #[test]
+    fn lost_connection_before_login() {
+        // Only the address is known
+        let s = "[20:41:25 INFO]: /127.0.0.1:5555 lost connection: Disconnected";
+        assert_eq!(
+            event(s),
+            unknown("/127.0.0.1:5555 lost connection: Disconnected")
+        );
+
+        // Half made game profile
+        let s = "[20:41:25 INFO]: com.mojang.authlib.GameProfile@1a2b3c[id=<null>,name=sally,properties={}] (/127.0.0.1:5555) lost connection: Disconnected";
+        assert_eq!(
+            event(s),
+            unknown(
+                "com.mojang.authlib.GameProfile@1a2b3c[id=<null>,name=sally,properties={}] (/127.0.0.1:5555) lost connection: Disconnected"
+            )
+        );
+    }
+
+    #[test]
+    fn chats() {
+        // Paper
+        let s = "[20:41:25 INFO]: <sally> hello";
+        assert_eq!(event(s), chat("sally", "hello"));
+
+        // Vanilla chat thread
+        let s = "[20:41:25] [Async Chat Thread - #0/INFO]: <sally> hello there";
+        assert_eq!(event(s), chat("sally", "hello there"));
+
+        // Unsigned message
+        let s = "[20:41:25 INFO]: [Not Secure] <sally> hello";
+        assert_eq!(event(s), chat("sally", "hello"));
+
+        // Empty message
+        let s = "[20:41:25 INFO]: <sally> ";
+        assert_eq!(event(s), chat("sally", ""));
+
+        // Looks like a join
+        let s = "[13:18:57 INFO]: <BalloonsAndPeople> Wiliam joined the game";
+        assert_eq!(
+            event(s),
+            chat("BalloonsAndPeople", "Wiliam joined the game")
+        );
+
+        // Looks like a join of someone else
+        let s = "[13:18:57 INFO]: <John> someone joined the game";
+        assert_eq!(event(s), chat("John", "someone joined the game"));
+
+        // Mentions joining
+        let s = "[13:18:57 INFO]: <Alice> I joined the game yesterday";
+        assert_eq!(event(s), chat("Alice", "I joined the game yesterday"));
+
+        // Looks like a leave
+        let s = "[13:18:57 INFO]: <Alice> bob left the game";
+        assert_eq!(event(s), chat("Alice", "bob left the game"));
+
+        // Looks like a death
+        let s = "[13:18:57 INFO]: <Alice> bob was slain by Zombie";
+        assert_eq!(event(s), chat("Alice", "bob was slain by Zombie"));
+
+        // Looks like the server starting
+        let s = "[13:18:57 INFO]: <Alice> Done (1.0s)! lol";
+        assert_eq!(event(s), chat("Alice", "Done (1.0s)! lol"));
+    }
+
+    #[test]
+    fn deaths() {
+        // Killed by a mob
+        let s = "[20:41:25 INFO]: sally was slain by Zombie";
+        assert_eq!(event(s), death("sally", "sally was slain by Zombie"));
+
+        // Shot
+        let s = "[20:41:25 INFO]: sally was shot by Skeleton";
+        assert_eq!(event(s), death("sally", "sally was shot by Skeleton"));
+
+        // Single word message
+        let s = "[20:41:25 INFO]: sally drowned";
+        assert_eq!(event(s), death("sally", "sally drowned"));
+
+        // Drowned while fleeing
+        let s = "[20:41:25 INFO]: sally drowned whilst trying to escape Zombie";
+        assert_eq!(
+            event(s),
+            death("sally", "sally drowned whilst trying to escape Zombie")
+        );
+
+        // Falling
+        let s = "[20:41:25 INFO]: sally fell from a high place";
+        assert_eq!(event(s), death("sally", "sally fell from a high place"));
+
+        // The void
+        let s = "[20:41:25 INFO]: sally fell out of the world";
+        assert_eq!(event(s), death("sally", "sally fell out of the world"));
+
+        // Fall damage
+        let s = "[20:41:25 INFO]: sally hit the ground too hard";
+        assert_eq!(event(s), death("sally", "sally hit the ground too hard"));
+
+        // Explosions
+        let s = "[20:41:25 INFO]: sally blew up";
+        assert_eq!(event(s), death("sally", "sally blew up"));
+        let s = "[20:41:25 INFO]: sally was blown up by Creeper";
+        assert_eq!(event(s), death("sally", "sally was blown up by Creeper"));
+
+        // Fire and lava
+        let s = "[20:41:25 INFO]: sally tried to swim in lava";
+        assert_eq!(event(s), death("sally", "sally tried to swim in lava"));
+        let s = "[20:41:25 INFO]: sally burned to death";
+        assert_eq!(event(s), death("sally", "sally burned to death"));
+
+        // Hunger
+        let s = "[20:41:25 INFO]: sally starved to death";
+        assert_eq!(event(s), death("sally", "sally starved to death"));
+
+        // Blocks
+        let s = "[20:41:25 INFO]: sally suffocated in a wall";
+        assert_eq!(event(s), death("sally", "sally suffocated in a wall"));
+
+        // Effects
+        let s = "[20:41:25 INFO]: sally was killed by magic";
+        assert_eq!(event(s), death("sally", "sally was killed by magic"));
+        let s = "[20:41:25 INFO]: sally withered away";
+        assert_eq!(event(s), death("sally", "sally withered away"));
+
+        // Elytra
+        let s = "[20:41:25 INFO]: sally experienced kinetic energy";
+        assert_eq!(event(s), death("sally", "sally experienced kinetic energy"));
+
+        // Generic death
+        let s = "[20:41:25 INFO]: sally died";
+        assert_eq!(event(s), death("sally", "sally died"));
+
+        // Name with caps, digits and an underscore
+        let s = "[20:41:25 INFO]: Sally_99 was squashed by a falling anvil";
+        assert_eq!(
+            event(s),
+            death("Sally_99", "Sally_99 was squashed by a falling anvil")
+        );
+    }
+
+    #[test]
+    fn not_deaths() {
+        // Plugin message
+        let s = "[20:41:25 INFO]: [Essentials] sally was slain by Zombie";
+        assert_eq!(event(s), unknown("[Essentials] sally was slain by Zombie"));
+
+        // World loading
+        let s = "[20:41:25 INFO]: Preparing level \"world\"";
+        assert_eq!(event(s), unknown("Preparing level \"world\""));
+
+        // Starts like a death message
+        let s = "[20:41:25 INFO]: sally fellow traveller";
+        assert_eq!(event(s), unknown("sally fellow traveller"));
+
+        // Login
+        let s = "[20:41:25 INFO]: sally[/127.0.0.1:5555] logged in with entity id 1 at (0.5, 64.0, 0.5)";
+        assert_eq!(
+            event(s),
+            unknown("sally[/127.0.0.1:5555] logged in with entity id 1 at (0.5, 64.0, 0.5)")
+        );
+    }
+
+    #[test]
+    fn advancements() {
+        // Advancement
+        let s = "[20:41:25] [Server thread/INFO]: sally has made the advancement [Stone Age]";
+        assert_eq!(
+            event(s),
+            advancement("sally", AdvancementKind::Advancement, "Stone Age")
+        );
+
+        // Goal
+        let s = "[20:41:25] [Server thread/INFO]: sally has reached the goal [Sky's the Limit]";
+        assert_eq!(
+            event(s),
+            advancement("sally", AdvancementKind::Goal, "Sky's the Limit")
+        );
+
+        // Challenge
+        let s = "[20:41:25] [Server thread/INFO]: sally has completed the challenge [How Did We Get Here?]";
+        assert_eq!(
+            event(s),
+            advancement("sally", AdvancementKind::Challenge, "How Did We Get Here?")
+        );
+
+        // Apostrophe in the title
+        let s =
+            "[20:41:25] [Server thread/INFO]: sally has made the advancement [Isn't It Iron Pick]";
+        assert_eq!(
+            event(s),
+            advancement("sally", AdvancementKind::Advancement, "Isn't It Iron Pick")
+        );
+    }
+
+    #[test]
+    fn lifecycle() {
+        // Starting
+        let s = "[20:41:25] [Server thread/INFO]: Starting minecraft server version 1.21.10";
+        assert_eq!(
+            event(s),
+            ServerEvent::ServerStarting {
+                version: "1.21.10".to_string(),
+            }
+        );
+
+        // Started, Paper
+        let s = "[20:41:25 INFO]: Done (3.456s)! For help, type \"help\"";
+        assert_eq!(
+            event(s),
+            ServerEvent::ServerStarted {
+                startup_time: Some(Duration::from_millis(3456)),
+            }
+        );
+
+        // Started, Vanilla
+        let s = "[20:41:25] [Server thread/INFO]: Done (12.5s)! For help, type \"help\" or \"?\"";
+        assert_eq!(
+            event(s),
+            ServerEvent::ServerStarted {
+                startup_time: Some(Duration::from_millis(12500)),
+            }
+        );
+
+        // Started, unreadable time
+        let s = "[20:41:25 INFO]: Done (abc)! For help, type \"help\"";
+        assert_eq!(event(s), ServerEvent::ServerStarted { startup_time: None });
+
+        // Stopping, Vanilla
+        let s = "[20:41:25] [Server thread/INFO]: Stopping the server";
+        assert_eq!(event(s), ServerEvent::ServerStopping);
+
+        // Stopping, Paper
+        let s = "[20:41:25 INFO]: Stopping server";
+        assert_eq!(event(s), ServerEvent::ServerStopping);
+    }
+
+    #[test]
+    fn warnings() {
+        // Paper
+        let s = "[20:41:25 WARN]: Can't keep up! Is the server overloaded? Running lots of ticks behind";
+        assert_eq!(
+            event(s),
+            warning("Can't keep up! Is the server overloaded? Running lots of ticks behind")
+        );
+
+        // Vanilla
+        let s = "[20:41:25] [Server thread/WARN]: sally moved too quickly! 12.3,0.0,4.5";
+        assert_eq!(event(s), warning("sally moved too quickly! 12.3,0.0,4.5"));
+
+        // Warnings are not mistaken for other events
+        let s = "[20:41:25 WARN]: sally died";
+        assert_eq!(event(s), warning("sally died"));
+    }
+
+    #[test]
+    fn lag() {
+        // Paper
+        let s = "[20:41:25 WARN]: Can't keep up! Is the server overloaded? Running 2500ms or 50 ticks behind";
+        assert_eq!(
+            event(s),
+            ServerEvent::Lag {
+                behind: Duration::from_millis(2500),
+                ticks: 50,
+            }
+        );
+
+        // Vanilla
+        let s = "[20:41:25] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 5012ms or 100 ticks behind";
+        assert_eq!(
+            event(s),
+            ServerEvent::Lag {
+                behind: Duration::from_millis(5012),
+                ticks: 100,
+            }
+        );
+
+        // Newer Vanilla
+        let s = "[20:41:25] [Server thread/WARN]: Can't keep up! Did the system time change, or is the server overloaded? Running 2035ms behind, skipping 40 tick(s)";
+        assert_eq!(
+            event(s),
+            ServerEvent::Lag {
+                behind: Duration::from_millis(2035),
+                ticks: 40,
+            }
+        );
+    }
+
+    #[test]
+    fn command_output() {
+        // /say
+        let s = "[20:41:25 INFO]: [Server] Restarting in 5 minutes";
+        assert_eq!(event(s), output("[Server] Restarting in 5 minutes"));
+
+        // /list
+        let s = "[20:41:25 INFO]: There are 2 of a max of 20 players online: sally, bob";
+        assert_eq!(
+            event(s),
+            output("There are 2 of a max of 20 players online: sally, bob")
+        );
+
+        // /whitelist
+        let s = "[20:41:25 INFO]: Added sally to the whitelist";
+        assert_eq!(event(s), output("Added sally to the whitelist"));
+        let s = "[20:41:25 INFO]: Removed sally from the whitelist";
+        assert_eq!(event(s), output("Removed sally from the whitelist"));
+        let s = "[20:41:25 INFO]: Player is already whitelisted";
+        assert_eq!(event(s), output("Player is already whitelisted"));
+
+        // /save-all and /save-off
+        let s = "[20:41:25 INFO]: Saved the game";
+        assert_eq!(event(s), output("Saved the game"));
+        let s = "[20:41:25 INFO]: Saving the game (this may take a moment!)";
+        assert_eq!(
+            event(s),
+            output("Saving the game (this may take a moment!)")
+        );
+        let s = "[20:41:25 INFO]: Automatic saving is now disabled";
+        assert_eq!(event(s), output("Automatic saving is now disabled"));
+
+        // /op
+        let s = "[20:41:25 INFO]: Made sally a server operator";
+        assert_eq!(event(s), output("Made sally a server operator"));
+
+        // /kick
+        let s = "[20:41:25 INFO]: Kicked sally: Kicked by an operator";
+        assert_eq!(event(s), output("Kicked sally: Kicked by an operator"));
+
+        // Bad command
+        let s = "[20:41:25 INFO]: Unknown or incomplete command, see below for error";
+        assert_eq!(
+            event(s),
+            output("Unknown or incomplete command, see below for error")
+        );
+
+        // Feedback broadcast to operators
+        let s = "[20:41:25 INFO]: [Rcon: Saved the game]";
+        assert_eq!(event(s), output("[Rcon: Saved the game]"));
+        let s = "[20:41:25 INFO]: [sally: Set own game mode to Creative Mode]";
+        assert_eq!(
+            event(s),
+            output("[sally: Set own game mode to Creative Mode]")
+        );
+    }
+
+    #[test]
+    fn unknown_lines() {
+        // Startup progress
+        let s = "[20:41:25 INFO]: Preparing spawn area: 83%";
+        assert_eq!(event(s), unknown("Preparing spawn area: 83%"));
+
+        // Plugins
+        let s = "[20:41:25 INFO]: [Essentials] Loading config file";
+        assert_eq!(event(s), unknown("[Essentials] Loading config file"));
+        let s = "[20:41:25 INFO]: [LuckPerms] [Info]: Loaded";
+        assert_eq!(event(s), unknown("[LuckPerms] [Info]: Loaded"));
+
+        // Login
+        let s = "[20:41:25 INFO]: UUID of player sally is 1234";
+        assert_eq!(event(s), unknown("UUID of player sally is 1234"));
+
+        // Looks like a death
+        let s = "[20:41:25 INFO]: Time elapsed: 1234 ms";
+        assert_eq!(event(s), unknown("Time elapsed: 1234 ms"));
+    }
+
+    #[test]
+    fn player_names() {
+        // Normal name
+        assert!(is_valid_player_name("sally"));
+
+        // Caps, digits and an underscore
+        assert!(is_valid_player_name("Sally_99"));
+
+        // Shortest and longest allowed names
+        assert!(is_valid_player_name("abc"));
+        assert!(is_valid_player_name("abcdefghijklmnop"));
+
+        // Too short
+        assert!(!is_valid_player_name("a"));
+        assert!(!is_valid_player_name(""));
+
+        // Too long
+        assert!(!is_valid_player_name("abcdefghijklmnopq"));
+
+        // Spaces and punctuation, like another command sneaked in
+        assert!(!is_valid_player_name("Sally Whiller"));
+        assert!(!is_valid_player_name("sally; op bob"));
+        assert!(!is_valid_player_name("[Server]"));
+        assert!(!is_valid_player_name("/127.0.0.1:5555"));
+
+        // Non ASCII letter
+        assert!(!is_valid_player_name("sälly"));
+    }
+}
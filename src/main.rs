@@ -1,18 +1,35 @@
 mod active_features;
+mod backup;
+mod chunk_store;
 mod commands;
+mod console;
 #[allow(async_fn_in_trait)]
 mod docker;
+mod lifecycle;
+mod log_parser;
+mod offsite;
+mod rcon;
+mod s3;
+mod scheduler;
+mod server_ping;
 mod server_state;
 mod sql;
+mod supervisor;
 
 use bollard::query_parameters::InspectContainerOptionsBuilder;
-use serenity::all::{Command, GuildId, Interaction};
+use serenity::all::{Command, GuildId, Interaction, Member, Message, User};
 use serenity::async_trait;
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
 use std::{env, sync::Arc};
 
-use crate::active_features::players::PlayerPresenceLog;
+use crate::active_features::lag::LagMonitor;
+use crate::active_features::links::PendingLinks;
+use crate::active_features::players::{OnlinePlayers, PlayerPresenceLog};
+use crate::active_features::presence::PresenceState;
+use crate::lifecycle::{Lifecycle, ServerLifecycle};
+use crate::rcon::RconClient;
+use crate::s3::S3Client;
 use crate::server_state::{BotConfig, ContextExt, ServerState, ServerStateMutables};
 
 struct Handler;
@@ -20,39 +37,151 @@ struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Component(component) = &interaction {
+            log::info!(
+                "Received component interaction: {}",
+                component.data.custom_id
+            );
+            let result = match component.data.custom_id.split(':').next() {
+                Some(commands::restart::CONFIRM_ID | commands::restart::CANCEL_ID) => {
+                    commands::restart::handle_component(&ctx, component).await
+                }
+                Some(
+                    commands::backup::RESTORE_CONFIRM_ID | commands::backup::RESTORE_CANCEL_ID,
+                ) => commands::backup::handle_component(&ctx, component).await,
+                _ => Ok(()),
+            };
+
+            if let Err(why) = result {
+                log::error!("Cannot respond to component interaction: {why}");
+            }
+            return;
+        }
+
         if let Interaction::Command(command) = interaction {
             log::info!("Received interaction command: {}", command.data.name);
             log::trace!("Received interaction command: {:#?}", command);
             let ctx = commands::Context::new(ctx, command);
 
+            match commands::permissions::is_allowed(&ctx).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::info!(
+                        "{} is not allowed to use {}",
+                        ctx.command.user.name,
+                        ctx.command.data.name
+                    );
+                    if let Err(why) = ctx
+                        .say_ephemeral(":no_entry: You do not have permission to use this command")
+                        .await
+                    {
+                        log::error!("Cannot respond to slash command: {why}");
+                    }
+                    if commands::audit::is_audited(&ctx.command.data.name)
+                        && let Err(why) = commands::audit::record(&ctx, "denied").await
+                    {
+                        log::error!("Cannot write audit log: {why}");
+                    }
+                    return;
+                }
+                Err(why) => {
+                    log::error!("Cannot check permissions: {why}");
+                    return;
+                }
+            }
+
             let result = match ctx.command.data.name.as_str() {
                 "ping" => commands::ping::run(&ctx).await,
                 "restart" => commands::restart::run(&ctx).await,
+                "start" => commands::start::run(&ctx).await,
+                "stop" => commands::stop::run(&ctx).await,
+                "kill" => commands::kill::run(&ctx).await,
                 "log" => commands::log::run(&ctx).await,
-                "snitch_channel_add" => commands::snitch::channel::add::run(&ctx).await,
-                "snitch_channel_remove" => commands::snitch::channel::remove::run(&ctx).await,
+                "playtime" => commands::playtime::run(&ctx).await,
+                "subscribe" => commands::subscriptions::subscribe::run(&ctx).await,
+                "unsubscribe" => commands::subscriptions::unsubscribe::run(&ctx).await,
                 "snitch_add" => commands::snitch::user::add::run(&ctx).await,
                 "snitch_remove" => commands::snitch::user::remove::run(&ctx).await,
+                "status" => commands::status::run(&ctx).await,
+                "permissions" => commands::permissions::run(&ctx).await,
+                "audit" => commands::audit::run(&ctx).await,
+                "stats" => commands::stats::run(&ctx).await,
+                "schedule" => commands::schedule::run(&ctx).await,
+                "console" => commands::console::run(&ctx).await,
+                "whitelist" => commands::whitelist::run(&ctx).await,
+                "link" => commands::links::link::run(&ctx).await,
+                "unlink" => commands::links::unlink::run(&ctx).await,
+                "backup" => commands::backup::run(&ctx).await,
                 _ => Ok(()),
             };
 
+            if commands::audit::is_audited(&ctx.command.data.name)
+                && !commands::restart::asks_confirmation(&ctx)
+            {
+                let outcome = match &result {
+                    Ok(()) => "ok".to_string(),
+                    Err(why) => why.to_string(),
+                };
+                if let Err(why) = commands::audit::record(&ctx, &outcome).await {
+                    log::error!("Cannot write audit log: {why}");
+                }
+            }
+
             if let Err(why) = result {
                 log::error!("Cannot respond to slash command: {why}");
             }
         }
     }
 
+    async fn message(&self, ctx: Context, msg: Message) {
+        let server_state = ctx.get_server_state().await;
+        if let Err(why) =
+            active_features::chat_bridge::relay_to_minecraft(&server_state, &ctx, &msg).await
+        {
+            log::error!("Cannot relay message to minecraft: {why}");
+        }
+    }
+
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _member_data_if_available: Option<Member>,
+    ) {
+        let server_state = ctx.get_server_state().await;
+        if let Err(why) =
+            active_features::whitelist::remove_departed_member(&server_state, guild_id, &user).await
+        {
+            log::error!("Cannot remove departed member from the whitelist: {why}");
+        }
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready) {
         log::info!("{} is connected!", ready.user.name);
 
         let public_commands = vec![
             commands::ping::register(),
             commands::restart::register(),
+            commands::start::register(),
+            commands::stop::register(),
+            commands::kill::register(),
             commands::log::register(),
-            commands::snitch::channel::add::register(),
-            commands::snitch::channel::remove::register(),
+            commands::playtime::register(),
+            commands::subscriptions::subscribe::register(),
+            commands::subscriptions::unsubscribe::register(),
             commands::snitch::user::add::register(),
             commands::snitch::user::remove::register(),
+            commands::status::register(),
+            commands::permissions::register(),
+            commands::audit::register(),
+            commands::stats::register(),
+            commands::schedule::register(),
+            commands::console::register(),
+            commands::whitelist::register(),
+            commands::links::link::register(),
+            commands::links::unlink::register(),
+            commands::backup::register(),
         ];
 
         // Guild (Server) specific commands
@@ -81,9 +210,13 @@ async fn main() {
 
     let token = env::var("DISCORD_TOKEN").expect("Expected DISCORD_TOKEN in environment");
 
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let bot_config = BotConfig::initialise();
+    let mut intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
+    if bot_config.whitelist_remove_on_leave {
+        intents |= GatewayIntents::GUILD_MEMBERS;
+    }
 
     let mut client = Client::builder(&token, intents)
         .event_handler(Handler)
@@ -91,9 +224,15 @@ async fn main() {
         .expect("Error creating client");
 
     let server_state = {
-        let bot_config = BotConfig::initialise();
         let mutables = ServerStateMutables {
             player_presence_log: PlayerPresenceLog::new(),
+            player_leave_log: PlayerPresenceLog::new(),
+            online_players: OnlinePlayers::new(),
+            presence: PresenceState::new(),
+            lifecycle: Lifecycle::new(ServerLifecycle::Stopped),
+            lag: LagMonitor::new(),
+            scheduled_restart: None,
+            pending_links: PendingLinks::new(),
         };
         let server_state = ServerState {
             docker: bollard::Docker::connect_with_local_defaults()
@@ -101,6 +240,34 @@ async fn main() {
             db: sqlx::PgPool::connect(&bot_config.db_addr)
                 .await
                 .expect("Could not connect to database"),
+            rcon: match (&bot_config.rcon_address, &bot_config.rcon_password) {
+                (Some(address), Some(password)) => Some(RconClient::new(address, password)),
+                (None, None) => None,
+                _ => panic!("RCON_ADDRESS and RCON_PASSWORD have to be set together"),
+            },
+            s3: match (
+                &bot_config.s3_endpoint,
+                &bot_config.s3_bucket,
+                &bot_config.s3_access_key_id,
+                &bot_config.s3_secret_access_key,
+            ) {
+                (Some(endpoint), Some(bucket), Some(access_key_id), Some(secret_access_key)) => {
+                    Some(S3Client::new(
+                        endpoint.parse().expect("S3_ENDPOINT was not a URL"),
+                        bucket,
+                        &bot_config.s3_region,
+                        access_key_id,
+                        secret_access_key,
+                    ))
+                }
+                (None, None, None, None) => None,
+                _ => panic!(
+                    "S3_ENDPOINT, S3_BUCKET, S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY have to be set together"
+                ),
+            },
+            shard_manager: client.shard_manager.clone(),
+            console_lines: tokio::sync::broadcast::channel(console::LINE_BUFFER).0,
+            backup_lock: tokio::sync::Mutex::new(()),
             bot_config,
             mutables: RwLock::new(mutables),
         };
@@ -126,29 +293,56 @@ async fn main() {
             )
         });
 
+    let container_state = container_inspect_response.state.unwrap_or_default();
+    // Assume a server that is already up has finished starting
+    let lifecycle_state = match (container_state.running, container_state.exit_code) {
+        (Some(true), _) => ServerLifecycle::Running,
+        (_, Some(0) | None) => ServerLifecycle::Stopped,
+        _ => ServerLifecycle::Crashed,
+    };
+    server_state.mutables.write().await.lifecycle = Lifecycle::new(lifecycle_state);
+
+    let container_started_at = container_state
+        .started_at
+        .and_then(|started_at| chrono::DateTime::parse_from_rfc3339(&started_at).ok())
+        .map(|started_at| started_at.to_utc());
+    sql::sessions::PlaySession::end_stale(container_started_at)
+        .execute(&server_state.db)
+        .await
+        .expect("Could not close stale play sessions");
+
     {
         let mut data = client.data.write().await;
         data.insert::<ServerState>(server_state.clone());
     }
 
-    {
-        let http_clone = client.http.clone();
-        let server_state_clone = server_state.clone();
-        tokio::task::spawn(async move {
-            let func = |s: String| {
-                let http = http_clone.clone();
-                let server_state = server_state.clone();
-                async move {
-                    // todo: add some sort of graceful shutdown or logging
-                    let _ =
-                        active_features::players::snitch_player_joined(&server_state, &http, &s)
-                            .await
-                            .map_err(|e| log::error!("Error in snitch_player_joined: {e}"));
-                }
-            };
-            docker::attach_and_listen(&server_state_clone, func).await
-        });
-    }
+    tokio::task::spawn(active_features::presence::refresh_periodically(
+        server_state.clone(),
+    ));
+
+    tokio::task::spawn(active_features::stats::record_periodically(
+        server_state.clone(),
+    ));
+    tokio::task::spawn(active_features::lag::check_recovery_periodically(
+        server_state.clone(),
+        client.http.clone(),
+    ));
+    tokio::task::spawn(active_features::container_events::watch(
+        server_state.clone(),
+        client.http.clone(),
+    ));
+    tokio::task::spawn(scheduler::run_periodically(
+        server_state.clone(),
+        client.http.clone(),
+    ));
+    tokio::task::spawn(backup::backup_periodically(
+        server_state.clone(),
+        client.http.clone(),
+    ));
+    tokio::task::spawn(supervisor::supervise(
+        server_state.clone(),
+        client.http.clone(),
+    ));
 
     if let Err(why) = client.start().await {
         log::error!("Client error: {:?}", why);
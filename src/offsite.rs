@@ -0,0 +1,161 @@
+//! Copies finished backups to an S3 compatible bucket, so they outlive the host's disk.
+//!
+//! Each backup is stored as `{S3_PREFIX}{name}/{archive}`, and the bucket is pruned with the
+//! same daily and weekly policy as the backup directory.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serenity::all::Http;
+use serenity::futures::StreamExt;
+use thiserror::Error;
+use tokio_util::io::StreamReader;
+
+use crate::{
+    active_features::alerts,
+    backup,
+    chunk_store::ChunkStore,
+    s3::{S3Client, S3Error},
+    server_state::ServerState,
+    sql::backups::BackupRecord,
+};
+
+#[derive(Error, Debug)]
+pub enum OffsiteError {
+    #[error("S3 error: {0}")]
+    S3(#[from] S3Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Backup {0} is not in the chunk store")]
+    NotFound(String),
+}
+
+pub fn is_enabled(server_state: &ServerState) -> bool {
+    server_state.s3.is_some() && server_state.bot_config.backup_dir.is_some()
+}
+
+/// The backup a key belongs to, like `20261018-120000` for `prefix/20261018-120000/world.tar`.
+fn backup_name<'a>(prefix: &str, key: &'a str) -> Option<&'a str> {
+    key.strip_prefix(prefix)?
+        .split_once('/')
+        .map(|(name, _)| name)
+}
+
+fn created_at(name: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(name, backup::NAME_FORMAT)
+        .ok()
+        .map(|created_at| created_at.and_utc())
+}
+
+/// Uploads every archive of the backup, then prunes the bucket.
+pub async fn upload(server_state: &ServerState, record: &BackupRecord) -> Result<(), OffsiteError> {
+    let (Some(s3), Some(backup_dir)) = (&server_state.s3, &server_state.bot_config.backup_dir)
+    else {
+        return Ok(());
+    };
+    let prefix = &server_state.bot_config.s3_prefix;
+
+    let store = ChunkStore::new(backup_dir);
+    let snapshot = store
+        .load_snapshot(&record.name)
+        .await?
+        .ok_or_else(|| OffsiteError::NotFound(record.name.clone()))?;
+    // Kept as whole archives, so the bucket can be restored from without the bot
+    for archive in &snapshot.archives {
+        let key = format!("{prefix}{}/{}", record.name, archive.name);
+        log::info!("Uploading {key} offsite");
+        s3.upload(
+            &key,
+            archive.size,
+            &archive.sha256,
+            StreamReader::new(store.read_archive(archive).boxed()),
+        )
+        .await?;
+    }
+
+    prune(server_state, s3).await
+}
+
+async fn prune(server_state: &ServerState, s3: &S3Client) -> Result<(), OffsiteError> {
+    let prefix = &server_state.bot_config.s3_prefix;
+    let keys = s3.list(prefix).await?;
+    let mut names: Vec<_> = keys
+        .iter()
+        .filter_map(|key| backup_name(prefix, key))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    // Anything that is not named like a backup was put there by someone else
+    let backups: Vec<_> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| Some((i as i64, created_at(name)?)))
+        .collect();
+    let prune: Vec<_> = backup::to_prune(
+        &backups,
+        server_state.bot_config.backup_keep_daily,
+        server_state.bot_config.backup_keep_weekly,
+    )
+    .into_iter()
+    .map(|i| names[i as usize])
+    .collect();
+
+    for key in keys
+        .iter()
+        .filter(|key| backup_name(prefix, key).is_some_and(|name| prune.contains(&name)))
+    {
+        log::info!("Deleting old offsite backup {key}");
+        s3.delete(key).await?;
+    }
+    Ok(())
+}
+
+/// Uploads the backup, telling the alert channel if it fails.
+pub async fn upload_and_alert(
+    server_state: &ServerState,
+    http: &Arc<Http>,
+    record: &BackupRecord,
+) -> Result<(), OffsiteError> {
+    let result = upload(server_state, record).await;
+    if let Err(e) = &result {
+        let _ = alerts::alert(
+            server_state,
+            http,
+            format!(":x: Uploading backup `#{}` offsite failed: {e}", record.id),
+        )
+        .await
+        .map_err(|e| log::error!("Error in alert: {e}"));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn backup_names() {
+        assert_eq!(
+            backup_name("mc/", "mc/20261018-120000/world.tar"),
+            Some("20261018-120000")
+        );
+        assert_eq!(
+            backup_name("", "20261018-120000/world.tar"),
+            Some("20261018-120000")
+        );
+        assert_eq!(backup_name("mc/", "other/20261018-120000/world.tar"), None);
+        assert_eq!(backup_name("mc/", "mc/notes.txt"), None);
+    }
+
+    #[test]
+    fn dates_from_names() {
+        assert_eq!(
+            created_at("20261018-120530"),
+            Some(Utc.with_ymd_and_hms(2026, 10, 18, 12, 5, 30).unwrap())
+        );
+        assert_eq!(created_at("photos"), None);
+    }
+}
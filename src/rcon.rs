@@ -0,0 +1,503 @@
+//! Client for the Source RCON protocol that Minecraft exposes with `enable-rcon=true`.
+//!
+//! Every packet is `length: i32, id: i32, type: i32, body, 0u8, 0u8` in little endian, where
+//! `length` counts everything after itself.
+//! Minecraft splits long responses over several packets without marking the last one, so each
+//! command is followed by a packet of an unknown type. The server answers requests in order, so
+//! the reply to that packet marks the end of the output.
+
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+const TYPE_RESPONSE_VALUE: i32 = 0;
+const TYPE_EXEC_COMMAND: i32 = 2;
+const TYPE_AUTH_RESPONSE: i32 = 2;
+const TYPE_AUTH: i32 = 3;
+
+/// id, type and the two null bytes
+const PACKET_OVERHEAD: usize = 10;
+/// Minecraft refuses command bodies longer than this
+const MAX_COMMAND_LENGTH: usize = 1446;
+/// Response bodies are split at 4096 bytes
+const MAX_RESPONSE_LENGTH: usize = 4096;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Timed out waiting for the server")]
+    Timeout,
+    #[error("Authentication failed, check the RCON password")]
+    AuthFailed,
+    #[error("Command is longer than {MAX_COMMAND_LENGTH} bytes")]
+    CommandTooLong,
+    #[error("Invalid packet: {0}")]
+    InvalidPacket(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+async fn write_packet(
+    stream: &mut (impl AsyncWrite + Unpin),
+    packet: &Packet,
+) -> Result<(), RconError> {
+    let length = (packet.body.len() + PACKET_OVERHEAD) as i32;
+    let mut buf = Vec::with_capacity(packet.body.len() + PACKET_OVERHEAD + 4);
+    buf.extend_from_slice(&length.to_le_bytes());
+    buf.extend_from_slice(&packet.id.to_le_bytes());
+    buf.extend_from_slice(&packet.kind.to_le_bytes());
+    buf.extend_from_slice(&packet.body);
+    buf.extend_from_slice(&[0, 0]);
+
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_packet(stream: &mut (impl AsyncRead + Unpin)) -> Result<Packet, RconError> {
+    let length = stream.read_i32_le().await?;
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|l| (PACKET_OVERHEAD..=MAX_RESPONSE_LENGTH + PACKET_OVERHEAD).contains(l))
+        .ok_or_else(|| RconError::InvalidPacket(format!("bad length {length}")))?;
+
+    let id = stream.read_i32_le().await?;
+    let kind = stream.read_i32_le().await?;
+    let mut body = vec![0; length - 8];
+    stream.read_exact(&mut body).await?;
+    if body.split_off(body.len() - 2) != [0, 0] {
+        return Err(RconError::InvalidPacket("missing terminator".to_string()));
+    }
+
+    Ok(Packet { id, kind, body })
+}
+
+async fn with_timeout<T>(
+    future: impl Future<Output = Result<T, RconError>>,
+) -> Result<T, RconError> {
+    tokio::time::timeout(TIMEOUT, future)
+        .await
+        .map_err(|_| RconError::Timeout)?
+}
+
+/// Keeps one authenticated connection open and reconnects when it breaks.
+pub struct RconClient {
+    address: String,
+    password: String,
+    connection: Mutex<Option<TcpStream>>,
+    next_id: AtomicI32,
+}
+
+impl RconClient {
+    pub fn new(address: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            password: password.into(),
+            connection: Mutex::new(None),
+            next_id: AtomicI32::new(1),
+        }
+    }
+
+    fn next_id(&self) -> i32 {
+        // Negative ids are reserved for failed authentication
+        self.next_id.fetch_add(1, Ordering::Relaxed) & i32::MAX
+    }
+
+    /// Runs a console command and returns its output.
+    pub async fn command(&self, command: &str) -> Result<String, RconError> {
+        if command.len() > MAX_COMMAND_LENGTH {
+            return Err(RconError::CommandTooLong);
+        }
+
+        let mut connection = self.connection.lock().await;
+        // A server restart closes the connection, which is best noticed before sending anything
+        if connection.as_ref().is_some_and(|stream| !is_open(stream)) {
+            log::debug!(
+                "RCON connection to {} was closed, reconnecting",
+                self.address
+            );
+            *connection = None;
+        }
+
+        let result = self.execute(&mut connection, command).await;
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+
+    async fn connect(&self) -> Result<TcpStream, RconError> {
+        log::debug!("Connecting to RCON at {}", self.address);
+        let mut stream =
+            with_timeout(async { Ok(TcpStream::connect(&self.address).await?) }).await?;
+
+        let id = self.next_id();
+        let auth = Packet {
+            id,
+            kind: TYPE_AUTH,
+            body: self.password.as_bytes().to_vec(),
+        };
+        with_timeout(async {
+            write_packet(&mut stream, &auth).await?;
+            loop {
+                let response = read_packet(&mut stream).await?;
+                // Source servers send an empty response value before the auth response
+                if response.kind != TYPE_AUTH_RESPONSE {
+                    continue;
+                }
+                return match response.id {
+                    -1 => Err(RconError::AuthFailed),
+                    response_id if response_id == id => Ok(()),
+                    response_id => Err(RconError::InvalidPacket(format!(
+                        "auth response for id {response_id}, expected {id}"
+                    ))),
+                };
+            }
+        })
+        .await?;
+
+        Ok(stream)
+    }
+
+    async fn execute(
+        &self,
+        connection: &mut Option<TcpStream>,
+        command: &str,
+    ) -> Result<String, RconError> {
+        let reused = connection.is_some();
+        let mut stream = match connection {
+            Some(stream) => stream,
+            None => connection.insert(self.connect().await?),
+        };
+
+        let id = self.next_id();
+        let packet = Packet {
+            id,
+            kind: TYPE_EXEC_COMMAND,
+            body: command.as_bytes().to_vec(),
+        };
+        match with_timeout(write_packet(stream, &packet)).await {
+            Ok(()) => {}
+            // The packet never made it out, so sending it again cannot run the command twice.
+            // Anything that fails after this point is not retried, `give` and the like are not
+            // safe to repeat.
+            Err(RconError::Io(e)) if reused => {
+                log::debug!(
+                    "RCON connection to {} broke, reconnecting: {e}",
+                    self.address
+                );
+                stream = connection.insert(self.connect().await?);
+                with_timeout(write_packet(stream, &packet)).await?;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let end_id = self.next_id();
+        with_timeout(async {
+            write_packet(
+                stream,
+                &Packet {
+                    id: end_id,
+                    kind: TYPE_RESPONSE_VALUE,
+                    body: Vec::new(),
+                },
+            )
+            .await?;
+
+            let mut output = Vec::new();
+            loop {
+                let response = read_packet(stream).await?;
+                match response.id {
+                    response_id if response_id == id => output.extend(response.body),
+                    response_id if response_id == end_id => break,
+                    response_id => log::warn!("Ignoring RCON packet with unknown id {response_id}"),
+                }
+            }
+
+            Ok(String::from_utf8_lossy(&output).into_owned())
+        })
+        .await
+    }
+}
+
+/// Whether the server still has the connection open, without waiting for anything.
+fn is_open(stream: &TcpStream) -> bool {
+    // Nothing is sent between commands, so anything but having to wait means it is unusable
+    matches!(
+        stream.try_read(&mut [0; 1]),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const PASSWORD: &str = "hunter2";
+
+    /// When the fake server closes a connection.
+    #[derive(Clone, Copy)]
+    enum Hangup {
+        Never,
+        /// Once this many commands have been answered, like a server restarting between commands
+        AfterAnswering(usize),
+        /// When this many more commands arrive, without running the last one
+        OnCommand(usize),
+    }
+
+    /// Behaves like the vanilla RCON listener. The output of every command is `respond(command)`.
+    async fn fake_server(
+        respond: fn(&str) -> String,
+        hangup: Hangup,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let connection_count = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                connection_count.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut authenticated = false;
+                    let mut commands = 0;
+                    while let Ok(packet) = read_packet(&mut stream).await {
+                        let response = match packet.kind {
+                            TYPE_AUTH => {
+                                authenticated = packet.body == PASSWORD.as_bytes();
+                                Packet {
+                                    id: if authenticated { packet.id } else { -1 },
+                                    kind: TYPE_AUTH_RESPONSE,
+                                    body: Vec::new(),
+                                }
+                            }
+                            TYPE_EXEC_COMMAND if authenticated => {
+                                commands += 1;
+                                if matches!(hangup, Hangup::OnCommand(n) if commands == n) {
+                                    return;
+                                }
+                                let command = String::from_utf8(packet.body).unwrap();
+                                let output = respond(&command).into_bytes();
+                                for chunk in output.chunks(MAX_RESPONSE_LENGTH) {
+                                    let response = Packet {
+                                        id: packet.id,
+                                        kind: TYPE_RESPONSE_VALUE,
+                                        body: chunk.to_vec(),
+                                    };
+                                    write_packet(&mut stream, &response).await.unwrap();
+                                }
+                                continue;
+                            }
+                            _ if authenticated => Packet {
+                                id: packet.id,
+                                kind: TYPE_RESPONSE_VALUE,
+                                body: format!("Unknown request {:x}", packet.kind).into_bytes(),
+                            },
+                            _ => return,
+                        };
+                        write_packet(&mut stream, &response).await.unwrap();
+                        if matches!(hangup, Hangup::AfterAnswering(n) if commands == n) {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, connections)
+    }
+
+    fn list(_: &str) -> String {
+        "There are 1 of a max of 20 players online: sally".to_string()
+    }
+
+    fn echo(command: &str) -> String {
+        command.to_string()
+    }
+
+    fn long(_: &str) -> String {
+        "a".repeat(MAX_RESPONSE_LENGTH * 2) + "end"
+    }
+
+    fn silent(_: &str) -> String {
+        String::new()
+    }
+
+    #[tokio::test]
+    async fn packet_round_trip() {
+        let packet = Packet {
+            id: 42,
+            kind: TYPE_EXEC_COMMAND,
+            body: b"list".to_vec(),
+        };
+        let mut buf = Vec::new();
+        write_packet(&mut buf, &packet).await.unwrap();
+
+        assert_eq!(
+            buf,
+            [
+                &14i32.to_le_bytes()[..],
+                &42i32.to_le_bytes(),
+                &2i32.to_le_bytes(),
+                b"list\0\0",
+            ]
+            .concat()
+        );
+        assert_eq!(read_packet(&mut buf.as_slice()).await.unwrap(), packet);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_packets() {
+        let too_short = [&4i32.to_le_bytes()[..], &[0; 4]].concat();
+        assert!(matches!(
+            read_packet(&mut too_short.as_slice()).await,
+            Err(RconError::InvalidPacket(_))
+        ));
+
+        let too_long = 1_000_000i32.to_le_bytes();
+        assert!(matches!(
+            read_packet(&mut too_long.as_slice()).await,
+            Err(RconError::InvalidPacket(_))
+        ));
+
+        let unterminated = [
+            &10i32.to_le_bytes()[..],
+            &1i32.to_le_bytes(),
+            &0i32.to_le_bytes(),
+            b"ab",
+        ]
+        .concat();
+        assert!(matches!(
+            read_packet(&mut unterminated.as_slice()).await,
+            Err(RconError::InvalidPacket(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn runs_commands() {
+        let (address, _) = fake_server(list, Hangup::Never).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        assert_eq!(
+            client.command("list").await.unwrap(),
+            "There are 1 of a max of 20 players online: sally"
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_connection() {
+        let (address, connections) = fake_server(echo, Hangup::Never).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        for command in ["say one", "say two", "say three"] {
+            assert_eq!(client.command(command).await.unwrap(), command);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_output() {
+        let (address, _) = fake_server(silent, Hangup::Never).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        assert_eq!(client.command("save-all").await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn joins_multi_packet_responses() {
+        let (address, _) = fake_server(long, Hangup::Never).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        let output = client.command("help").await.unwrap();
+        assert_eq!(output.len(), MAX_RESPONSE_LENGTH * 2 + 3);
+        assert!(output.ends_with("aend"));
+    }
+
+    #[tokio::test]
+    async fn wrong_password() {
+        let (address, _) = fake_server(list, Hangup::Never).await;
+        let client = RconClient::new(address, "wrong");
+
+        assert!(matches!(
+            client.command("list").await,
+            Err(RconError::AuthFailed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_disconnect() {
+        let (address, connections) = fake_server(echo, Hangup::AfterAnswering(1)).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        assert_eq!(client.command("say one").await.unwrap(), "say one");
+        // Gives the server time to close the connection
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(client.command("say two").await.unwrap(), "say two");
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_resend_commands() {
+        let (address, connections) = fake_server(echo, Hangup::OnCommand(2)).await;
+        let client = RconClient::new(address, PASSWORD);
+
+        assert_eq!(client.command("say one").await.unwrap(), "say one");
+        // The server may have run it before the connection went, so it is not sent again
+        assert!(matches!(
+            client.command("give sally diamond").await,
+            Err(RconError::Io(_))
+        ));
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // The next command gets a new connection
+        assert_eq!(client.command("say three").await.unwrap(), "say three");
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn no_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let client = RconClient::new(address, PASSWORD);
+
+        assert!(matches!(
+            client.command("list").await,
+            Err(RconError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn command_too_long() {
+        let client = RconClient::new("127.0.0.1:1", PASSWORD);
+
+        assert!(matches!(
+            client.command(&"a".repeat(MAX_COMMAND_LENGTH + 1)).await,
+            Err(RconError::CommandTooLong)
+        ));
+    }
+}
@@ -0,0 +1,506 @@
+//! Runs the recurring schedules admins set up with `/schedule`.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Http, UserId};
+
+use crate::{
+    active_features::alerts, commands::audit, console, lifecycle, server_state::ServerState,
+    sql::schedules::ScheduleRow,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Restarts are started early so the players get the whole countdown
+const RESTART_WARNING: TimeDelta = TimeDelta::minutes(5);
+/// Runs missed by more than this, like while the bot was down, are skipped
+const GRACE_PERIOD: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduledAction {
+    Restart,
+    Console(String),
+    Message {
+        channel_id: ChannelId,
+        content: String,
+    },
+}
+
+impl ScheduledAction {
+    pub const NAMES: [&str; 3] = ["restart", "console", "message"];
+
+    pub fn new(
+        name: &str,
+        argument: Option<String>,
+        channel_id: Option<ChannelId>,
+    ) -> Option<Self> {
+        match name {
+            "restart" => Some(Self::Restart),
+            "console" => Some(Self::Console(argument?)),
+            "message" => Some(Self::Message {
+                channel_id: channel_id?,
+                content: argument?,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Restart => "restart",
+            Self::Console(_) => "console",
+            Self::Message { .. } => "message",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Restart => "restart the server".to_string(),
+            Self::Console(command) => format!("run `{command}`"),
+            Self::Message {
+                channel_id,
+                content,
+            } => format!("post \"{content}\" in <#{channel_id}>"),
+        }
+    }
+
+    /// How long before the scheduled time the action is started.
+    fn lead(&self) -> TimeDelta {
+        match self {
+            Self::Restart => RESTART_WARNING,
+            _ => TimeDelta::zero(),
+        }
+    }
+}
+
+/// Choices for the day of a schedule, `None` is every day.
+pub const DAYS: [(&str, Option<Weekday>); 8] = [
+    ("daily", None),
+    ("monday", Some(Weekday::Mon)),
+    ("tuesday", Some(Weekday::Tue)),
+    ("wednesday", Some(Weekday::Wed)),
+    ("thursday", Some(Weekday::Thu)),
+    ("friday", Some(Weekday::Fri)),
+    ("saturday", Some(Weekday::Sat)),
+    ("sunday", Some(Weekday::Sun)),
+];
+
+pub fn weekday_name(weekday: Option<Weekday>) -> &'static str {
+    DAYS.iter()
+        .find(|(_, day)| *day == weekday)
+        .map(|(name, _)| *name)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, PartialEq)]
+enum Due {
+    Run(DateTime<Utc>),
+    Missed(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub created_by: UserId,
+    pub weekday: Option<Weekday>,
+    pub time: NaiveTime,
+    pub timezone: Tz,
+    pub action: ScheduledAction,
+    pub last_run_at: DateTime<Utc>,
+}
+
+impl Schedule {
+    fn from_row(row: ScheduleRow) -> Option<Self> {
+        let weekday = match row.weekday {
+            Some(day) => Some(Weekday::try_from(u8::try_from(day).ok()?).ok()?),
+            None => None,
+        };
+        Some(Self {
+            id: row.id,
+            guild_id: GuildId::new(row.guild_id.get()),
+            created_by: UserId::new(row.created_by.get()),
+            weekday,
+            time: row.time,
+            timezone: row.timezone.parse().ok()?,
+            action: ScheduledAction::new(
+                &row.action,
+                row.argument,
+                row.channel_id.map(|id| ChannelId::new(id.get())),
+            )?,
+            last_run_at: row.last_run_at,
+        })
+    }
+
+    pub fn to_row(&self, created_at: DateTime<Utc>) -> ScheduleRow {
+        let (argument, channel_id) = match &self.action {
+            ScheduledAction::Restart => (None, None),
+            ScheduledAction::Console(command) => (Some(command.clone()), None),
+            ScheduledAction::Message {
+                channel_id,
+                content,
+            } => (Some(content.clone()), Some(channel_id.get().into())),
+        };
+        ScheduleRow {
+            id: self.id,
+            guild_id: self.guild_id.get().into(),
+            created_by: self.created_by.get().into(),
+            created_at,
+            weekday: self.weekday.map(|day| day.num_days_from_monday() as i16),
+            time: self.time,
+            timezone: self.timezone.name().to_string(),
+            action: self.action.name().to_string(),
+            argument,
+            channel_id,
+            last_run_at: self.last_run_at,
+        }
+    }
+
+    pub async fn get_all(server_state: &ServerState) -> sqlx::Result<Vec<Schedule>> {
+        Ok(Self::parse_rows(
+            ScheduleRow::get_all(&server_state.db).await?,
+        ))
+    }
+
+    pub async fn get_for_guild(
+        server_state: &ServerState,
+        guild_id: GuildId,
+    ) -> sqlx::Result<Vec<Schedule>> {
+        Ok(Self::parse_rows(
+            ScheduleRow::get_for_guild(&server_state.db, guild_id).await?,
+        ))
+    }
+
+    fn parse_rows(rows: Vec<ScheduleRow>) -> Vec<Schedule> {
+        rows.into_iter()
+            .filter_map(|row| {
+                let id = row.id;
+                let schedule = Self::from_row(row);
+                if schedule.is_none() {
+                    log::error!("Ignoring schedule {id}, it could not be read");
+                }
+                schedule
+            })
+            .collect()
+    }
+
+    /// The first time the schedule fires after `after`.
+    pub fn next_run(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        let mut date = after.with_timezone(&self.timezone).date_naive();
+        loop {
+            if self.weekday.is_none_or(|day| date.weekday() == day) {
+                let local = date.and_time(self.time);
+                // A time skipped by a daylight saving change runs an hour later instead
+                let at = self
+                    .timezone
+                    .from_local_datetime(&local)
+                    .earliest()
+                    .or_else(|| {
+                        self.timezone
+                            .from_local_datetime(&(local + TimeDelta::hours(1)))
+                            .earliest()
+                    });
+                if let Some(at) = at.map(|at| at.to_utc())
+                    && at > after
+                {
+                    return at;
+                }
+            }
+            date = date.succ_opt().expect("date in range");
+        }
+    }
+
+    /// Whether the schedule has to be run, only looking at the latest time that is due.
+    fn due(&self, now: DateTime<Utc>) -> Option<Due> {
+        let lead = self.action.lead();
+        let mut next = self.next_run(self.last_run_at);
+        if now < next - lead {
+            return None;
+        }
+        loop {
+            let after = self.next_run(next);
+            if now < after - lead {
+                break;
+            }
+            next = after;
+        }
+
+        if now > next + GRACE_PERIOD {
+            Some(Due::Missed(next))
+        } else {
+            Some(Due::Run(next))
+        }
+    }
+
+    async fn run(
+        &self,
+        server_state: &Arc<ServerState>,
+        http: &Arc<Http>,
+        at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        match &self.action {
+            ScheduledAction::Restart => {
+                let delay = (at - Utc::now()).to_std().unwrap_or_default();
+                lifecycle::schedule_restart(server_state, http.clone(), delay)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            ScheduledAction::Console(command) => {
+                console::check_permitted(&server_state.bot_config, command)
+                    .map_err(|e| e.to_string())?;
+                console::send_command(server_state, command)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            ScheduledAction::Message {
+                channel_id,
+                content,
+            } => {
+                channel_id
+                    .send_message(
+                        http,
+                        CreateMessage::new()
+                            .content(content)
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn check(server_state: &Arc<ServerState>, http: &Arc<Http>) -> sqlx::Result<()> {
+    let now = Utc::now();
+    for schedule in Schedule::get_all(server_state).await? {
+        let (at, missed) = match schedule.due(now) {
+            None => continue,
+            Some(Due::Missed(at)) => (at, true),
+            Some(Due::Run(at)) => (at, false),
+        };
+
+        // Recorded first, so a failed write skips the action instead of running it every check
+        if let Err(e) = ScheduleRow::set_last_run(schedule.id, at)
+            .execute(&server_state.db)
+            .await
+        {
+            log::error!("Could not record that schedule {} ran: {e}", schedule.id);
+            continue;
+        }
+
+        if missed {
+            log::warn!("Skipping schedule {} that was due at {at}", schedule.id);
+            continue;
+        }
+        log::info!(
+            "Running schedule {}: {}",
+            schedule.id,
+            schedule.action.describe()
+        );
+        let result = schedule.run(server_state, http, at).await;
+        let outcome = match &result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => e.clone(),
+        };
+        // Under the name of whoever made the schedule, the user may have left since
+        let user_name = match schedule.created_by.to_user(http).await {
+            Ok(user) => user.name,
+            Err(_) => schedule.created_by.to_string(),
+        };
+        if let Err(e) = audit::write(
+            server_state,
+            Some(schedule.guild_id),
+            schedule.created_by,
+            &user_name,
+            "schedule",
+            format!("run id:{} action:{}", schedule.id, schedule.action.name()),
+            &outcome,
+        )
+        .await
+        {
+            log::error!("Cannot write audit log: {e}");
+        }
+
+        if let Err(e) = result {
+            let _ = alerts::alert(
+                server_state,
+                http,
+                format!(
+                    ":x: Scheduled action #{} to {} failed: {e}",
+                    schedule.id,
+                    schedule.action.describe()
+                ),
+            )
+            .await
+            .map_err(|e| log::error!("Error in alert: {e}"));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the schedules for as long as the bot runs, they are read from the database every time.
+pub async fn run_periodically(server_state: Arc<ServerState>, http: Arc<Http>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&server_state, &http).await {
+            log::error!("Could not check schedules: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(weekday: Option<Weekday>, time: &str, action: ScheduledAction) -> Schedule {
+        Schedule {
+            id: 1,
+            guild_id: GuildId::new(1),
+            created_by: UserId::new(1),
+            weekday,
+            time: NaiveTime::parse_from_str(time, "%H:%M").unwrap(),
+            timezone: chrono_tz::Europe::Stockholm,
+            action,
+            last_run_at: utc(2026, 10, 14, 0, 0),
+        }
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn next_run_daily() {
+        let daily = schedule(None, "05:00", ScheduledAction::Restart);
+        // Stockholm is UTC+2 in summer time
+        assert_eq!(
+            daily.next_run(utc(2026, 10, 14, 0, 0)),
+            utc(2026, 10, 14, 3, 0)
+        );
+        assert_eq!(
+            daily.next_run(utc(2026, 10, 14, 3, 0)),
+            utc(2026, 10, 15, 3, 0)
+        );
+        // And UTC+1 after the clocks go back on the 25th
+        assert_eq!(
+            daily.next_run(utc(2026, 10, 25, 12, 0)),
+            utc(2026, 10, 26, 4, 0)
+        );
+    }
+
+    #[test]
+    fn next_run_weekly() {
+        let sunday = schedule(
+            Some(Weekday::Sun),
+            "18:30",
+            ScheduledAction::Console("say hi".to_string()),
+        );
+        // The 14th is a wednesday
+        assert_eq!(
+            sunday.next_run(utc(2026, 10, 14, 0, 0)),
+            utc(2026, 10, 18, 16, 30)
+        );
+        assert_eq!(
+            sunday.next_run(utc(2026, 10, 18, 16, 30)),
+            utc(2026, 10, 25, 17, 30)
+        );
+    }
+
+    #[test]
+    fn next_run_daylight_saving_gap() {
+        // 02:30 does not exist in Stockholm on the 29th of march
+        let daily = schedule(None, "02:30", ScheduledAction::Restart);
+        assert_eq!(
+            daily.next_run(utc(2026, 3, 28, 12, 0)),
+            utc(2026, 3, 29, 1, 30)
+        );
+        assert_eq!(
+            daily.next_run(utc(2026, 3, 29, 1, 30)),
+            utc(2026, 3, 30, 0, 30)
+        );
+    }
+
+    #[test]
+    fn due() {
+        let message = schedule(
+            None,
+            "05:00",
+            ScheduledAction::Message {
+                channel_id: ChannelId::new(1),
+                content: "Good morning".to_string(),
+            },
+        );
+        let restart = schedule(None, "05:00", ScheduledAction::Restart);
+
+        assert_eq!(message.due(utc(2026, 10, 14, 2, 57)), None);
+        assert_eq!(restart.due(utc(2026, 10, 14, 2, 54)), None);
+        assert_eq!(
+            restart.due(utc(2026, 10, 14, 2, 57)),
+            Some(Due::Run(utc(2026, 10, 14, 3, 0)))
+        );
+        assert_eq!(
+            message.due(utc(2026, 10, 14, 3, 5)),
+            Some(Due::Run(utc(2026, 10, 14, 3, 0)))
+        );
+        assert_eq!(
+            message.due(utc(2026, 10, 14, 3, 30)),
+            Some(Due::Missed(utc(2026, 10, 14, 3, 0)))
+        );
+        // Only the latest run counts after the bot was down for days
+        assert_eq!(
+            message.due(utc(2026, 10, 17, 3, 1)),
+            Some(Due::Run(utc(2026, 10, 17, 3, 0)))
+        );
+    }
+
+    #[test]
+    fn actions() {
+        let text = || Some("hello".to_string());
+
+        // Restart takes nothing
+        let action = ScheduledAction::new("restart", None, None);
+        assert_eq!(action, Some(ScheduledAction::Restart));
+        assert_eq!(action.unwrap().name(), "restart");
+
+        // Console needs a command
+        let action = ScheduledAction::new("console", text(), None);
+        assert_eq!(action, Some(ScheduledAction::Console("hello".to_string())));
+        assert_eq!(action.unwrap().name(), "console");
+        assert_eq!(ScheduledAction::new("console", None, None), None);
+
+        // Message needs text and a channel
+        let action = ScheduledAction::new("message", text(), Some(ChannelId::new(5)));
+        assert_eq!(
+            action,
+            Some(ScheduledAction::Message {
+                channel_id: ChannelId::new(5),
+                content: "hello".to_string(),
+            })
+        );
+        assert_eq!(action.unwrap().name(), "message");
+        assert_eq!(ScheduledAction::new("message", text(), None), None);
+
+        // Unknown action
+        assert_eq!(ScheduledAction::new("reboot", None, None), None);
+    }
+
+    #[test]
+    fn round_trips_through_row() {
+        let weekly = schedule(
+            Some(Weekday::Sun),
+            "18:30",
+            ScheduledAction::Message {
+                channel_id: ChannelId::new(5),
+                content: "Event night".to_string(),
+            },
+        );
+        let row = weekly.to_row(utc(2026, 10, 14, 0, 0));
+        assert_eq!(row.weekday, Some(6));
+        assert_eq!(row.timezone, "Europe/Stockholm");
+        assert_eq!(Schedule::from_row(row), Some(weekly));
+    }
+}
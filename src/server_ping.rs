@@ -0,0 +1,423 @@
+//! Client for the Server List Ping protocol the multiplayer screen uses to show a server.
+//!
+//! Packets are framed as `length: VarInt, id: VarInt, data`. The client sends a handshake and a
+//! status request, the server answers with a JSON document, and a ping/pong pair measures latency.
+
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// By convention a client that does not know the server version sends -1
+const PROTOCOL_VERSION: i32 = -1;
+const NEXT_STATE_STATUS: i32 = 1;
+const PACKET_HANDSHAKE: i32 = 0x00;
+const PACKET_STATUS: i32 = 0x00;
+const PACKET_PING: i32 = 0x01;
+/// Status responses are a single string of at most 32767 characters
+const MAX_PACKET_LENGTH: usize = 4 * 32767 + 16;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_PORT: u16 = 25565;
+
+#[derive(Error, Debug)]
+pub enum PingError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Timed out waiting for the server")]
+    Timeout,
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Invalid status JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub version: String,
+    pub online_players: u64,
+    pub max_players: u64,
+    /// Some servers hide the player list, or only send part of it
+    pub player_sample: Vec<String>,
+    pub motd: String,
+    pub latency: Duration,
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+async fn read_varint(stream: &mut (impl AsyncRead + Unpin)) -> Result<i32, PingError> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(PingError::InvalidResponse("VarInt is too long".to_string()))
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_varint(buf, s.len() as i32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn frame(id: i32, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 5);
+    write_varint(&mut body, id);
+    body.extend_from_slice(data);
+
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend(body);
+    packet
+}
+
+/// Reads one packet and returns its id and data.
+async fn read_packet(stream: &mut (impl AsyncRead + Unpin)) -> Result<(i32, Vec<u8>), PingError> {
+    let length = read_varint(stream).await?;
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|l| (1..=MAX_PACKET_LENGTH).contains(l))
+        .ok_or_else(|| PingError::InvalidResponse(format!("bad packet length {length}")))?;
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+
+    let mut body = body.as_slice();
+    let id = read_varint(&mut body).await?;
+    Ok((id, body.to_vec()))
+}
+
+/// Splits `host:port` or `[ipv6]:port` into the host and port, the port is optional.
+fn split_address(address: &str) -> (&str, u16) {
+    if let Some(bracketed) = address.strip_prefix('[')
+        && let Some((host, rest)) = bracketed.split_once(']')
+    {
+        let port = rest.strip_prefix(':').and_then(|port| port.parse().ok());
+        return (host, port.unwrap_or(DEFAULT_PORT));
+    }
+    match address.split_once(':') {
+        // Any other colon means a bare IPv6 address
+        Some((host, port)) if !port.contains(':') => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => (address, DEFAULT_PORT),
+        },
+        _ => (address, DEFAULT_PORT),
+    }
+}
+
+/// Asks the server at `address` for its status, like the multiplayer screen does.
+pub async fn ping(address: &str) -> Result<ServerStatus, PingError> {
+    tokio::time::timeout(TIMEOUT, ping_inner(address))
+        .await
+        .map_err(|_| PingError::Timeout)?
+}
+
+async fn ping_inner(address: &str) -> Result<ServerStatus, PingError> {
+    let (host, port) = split_address(address);
+    let mut stream = TcpStream::connect((host, port)).await?;
+
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, PROTOCOL_VERSION);
+    write_string(&mut handshake, host);
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, NEXT_STATE_STATUS);
+    stream
+        .write_all(&frame(PACKET_HANDSHAKE, &handshake))
+        .await?;
+    stream.write_all(&frame(PACKET_STATUS, &[])).await?;
+
+    let (id, data) = read_packet(&mut stream).await?;
+    if id != PACKET_STATUS {
+        return Err(PingError::InvalidResponse(format!(
+            "expected status packet, got {id:#x}"
+        )));
+    }
+    let mut data = data.as_slice();
+    let json_length = read_varint(&mut data).await?;
+    let json = usize::try_from(json_length)
+        .ok()
+        .and_then(|l| data.get(..l))
+        .ok_or_else(|| PingError::InvalidResponse("truncated status".to_string()))?;
+    let json = std::str::from_utf8(json)
+        .map_err(|e| PingError::InvalidResponse(format!("status is not UTF-8: {e}")))?;
+
+    let payload = chrono::Utc::now().timestamp_millis();
+    let sent = Instant::now();
+    stream
+        .write_all(&frame(PACKET_PING, &payload.to_be_bytes()))
+        .await?;
+    let (id, data) = read_packet(&mut stream).await?;
+    let latency = sent.elapsed();
+    if id != PACKET_PING || data != payload.to_be_bytes() {
+        return Err(PingError::InvalidResponse(
+            "pong did not match ping".to_string(),
+        ));
+    }
+
+    parse_status(json, latency)
+}
+
+fn parse_status(json: &str, latency: Duration) -> Result<ServerStatus, PingError> {
+    let status: serde_json::Value = serde_json::from_str(json)?;
+    let players = &status["players"];
+
+    Ok(ServerStatus {
+        version: status["version"]["name"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string(),
+        online_players: players["online"].as_u64().unwrap_or(0),
+        max_players: players["max"].as_u64().unwrap_or(0),
+        player_sample: players["sample"]
+            .as_array()
+            .map(|sample| {
+                sample
+                    .iter()
+                    .filter_map(|player| player["name"].as_str())
+                    .map(strip_formatting)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        motd: strip_formatting(&chat_text(&status["description"])),
+        latency,
+    })
+}
+
+/// Flattens a chat component (a string, an array or an object with `text` and `extra`).
+fn chat_text(component: &serde_json::Value) -> String {
+    match component {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(components) => components.iter().map(chat_text).collect(),
+        serde_json::Value::Object(object) => {
+            let mut text = object
+                .get("text")
+                .and_then(|t| t.as_str())
+                .unwrap_or_default()
+                .to_string();
+            if let Some(extra) = object.get("extra") {
+                text.push_str(&chat_text(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+/// Removes legacy `§x` colour and style codes.
+fn strip_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Answers a single status request with `json`, like a vanilla server.
+    async fn fake_server(json: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let (id, data) = read_packet(&mut stream).await.unwrap();
+            assert_eq!(id, PACKET_HANDSHAKE);
+            assert_eq!(*data.last().unwrap(), NEXT_STATE_STATUS as u8);
+
+            let (id, data) = read_packet(&mut stream).await.unwrap();
+            assert_eq!((id, data.len()), (PACKET_STATUS, 0));
+            let mut response = Vec::new();
+            write_string(&mut response, json);
+            stream
+                .write_all(&frame(PACKET_STATUS, &response))
+                .await
+                .unwrap();
+
+            let (id, data) = read_packet(&mut stream).await.unwrap();
+            assert_eq!(id, PACKET_PING);
+            stream.write_all(&frame(PACKET_PING, &data)).await.unwrap();
+        });
+
+        address
+    }
+
+    #[test]
+    fn varints() {
+        let varint = |value| {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            buf
+        };
+
+        // Zero
+        assert_eq!(varint(0), [0x00]);
+
+        // Single byte
+        assert_eq!(varint(1), [0x01]);
+        assert_eq!(varint(127), [0x7f]);
+
+        // Two bytes
+        assert_eq!(varint(128), [0x80, 0x01]);
+        assert_eq!(varint(255), [0xff, 0x01]);
+
+        // The default port
+        assert_eq!(varint(25565), [0xdd, 0xc7, 0x01]);
+
+        // Largest value
+        assert_eq!(varint(i32::MAX), [0xff, 0xff, 0xff, 0xff, 0x07]);
+
+        // Negative values always take five bytes
+        assert_eq!(varint(-1), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn addresses() {
+        // Host and port
+        assert_eq!(
+            split_address("mc.example.com:25566"),
+            ("mc.example.com", 25566)
+        );
+
+        // No port
+        assert_eq!(
+            split_address("mc.example.com"),
+            ("mc.example.com", DEFAULT_PORT)
+        );
+
+        // IPv4
+        assert_eq!(split_address("127.0.0.1:25566"), ("127.0.0.1", 25566));
+
+        // Bracketed IPv6 with and without a port
+        assert_eq!(split_address("[::1]:25566"), ("::1", 25566));
+        assert_eq!(split_address("[::1]"), ("::1", DEFAULT_PORT));
+
+        // Bare IPv6, the last group is not a port
+        assert_eq!(split_address("::1"), ("::1", DEFAULT_PORT));
+        assert_eq!(
+            split_address("2001:db8::25"),
+            ("2001:db8::25", DEFAULT_PORT)
+        );
+    }
+
+    #[tokio::test]
+    async fn varint_round_trip() {
+        for value in [0, 1, 300, 25565, 2097151, i32::MAX, -1, i32::MIN] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            assert_eq!(read_varint(&mut buf.as_slice()).await.unwrap(), value);
+        }
+    }
+
+    #[tokio::test]
+    async fn varint_too_long() {
+        let bytes = [0xff; 6];
+        assert!(matches!(
+            read_varint(&mut bytes.as_slice()).await,
+            Err(PingError::InvalidResponse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn pings_server() {
+        let address = fake_server(
+            r#"{
+                "version": {"name": "Paper 1.21.10", "protocol": 773},
+                "players": {"max": 20, "online": 2, "sample": [
+                    {"name": "sally", "id": "4566e69f-c907-48ee-8d71-d7ba5aa00d20"},
+                    {"name": "bob", "id": "4566e69f-c907-48ee-8d71-d7ba5aa00d21"}
+                ]},
+                "description": "A Minecraft Server"
+            }"#,
+        )
+        .await;
+
+        let status = ping(&address).await.unwrap();
+        assert_eq!(status.version, "Paper 1.21.10");
+        assert_eq!((status.online_players, status.max_players), (2, 20));
+        assert_eq!(status.player_sample, vec!["sally", "bob"]);
+        assert_eq!(status.motd, "A Minecraft Server");
+        assert!(status.latency < TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn pings_empty_server() {
+        let address = fake_server(
+            r#"{
+                "version": {"name": "1.21.10", "protocol": 773},
+                "players": {"max": 20, "online": 0},
+                "description": {"text": ""}
+            }"#,
+        )
+        .await;
+
+        let status = ping(&address).await.unwrap();
+        assert_eq!(status.online_players, 0);
+        assert!(status.player_sample.is_empty());
+        assert_eq!(status.motd, "");
+    }
+
+    #[tokio::test]
+    async fn no_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        assert!(matches!(ping(&address).await, Err(PingError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn invalid_json() {
+        let address = fake_server("not json").await;
+
+        assert!(matches!(ping(&address).await, Err(PingError::Json(_))));
+    }
+
+    #[test]
+    fn chat_component_motd() {
+        let status = parse_status(
+            r#"{"description": {"text": "§aWelcome ", "extra": [
+                {"text": "to ", "bold": true},
+                {"text": "§lthe server", "extra": ["!"]}
+            ]}}"#,
+            Duration::ZERO,
+        )
+        .unwrap();
+
+        assert_eq!(status.motd, "Welcome to the server!");
+        assert_eq!(status.version, "unknown");
+    }
+
+    #[test]
+    fn strips_formatting_codes() {
+        assert_eq!(strip_formatting("§6§lGold§r text"), "Gold text");
+        assert_eq!(strip_formatting("trailing §"), "trailing ");
+        assert_eq!(strip_formatting("plain"), "plain");
+    }
+}
@@ -1,10 +1,19 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use bollard::Docker;
-use serenity::{all::Context, prelude::TypeMapKey};
-use tokio::sync::RwLock;
+use serenity::{
+    all::{Context, ShardManager},
+    prelude::TypeMapKey,
+};
+use tokio::sync::{Mutex, RwLock, broadcast};
 
-use crate::active_features::players::PlayerPresenceLog;
+use crate::active_features::lag::LagMonitor;
+use crate::active_features::links::PendingLinks;
+use crate::active_features::players::{OnlinePlayers, PlayerPresenceLog};
+use crate::active_features::presence::PresenceState;
+use crate::lifecycle::{Lifecycle, ScheduledRestart};
+use crate::rcon::RconClient;
+use crate::s3::S3Client;
 
 macro_rules! env_expect {
     ($env_name:literal) => {
@@ -15,12 +24,27 @@ macro_rules! env_expect {
 
 pub struct ServerStateMutables {
     pub player_presence_log: PlayerPresenceLog,
+    pub player_leave_log: PlayerPresenceLog,
+    pub online_players: OnlinePlayers,
+    pub presence: PresenceState,
+    pub lifecycle: Lifecycle,
+    pub scheduled_restart: Option<ScheduledRestart>,
+    pub lag: LagMonitor,
+    pub pending_links: PendingLinks,
 }
 
 pub struct ServerState {
     pub bot_config: BotConfig,
     pub docker: Docker,
     pub db: sqlx::Pool<sqlx::Postgres>,
+    pub rcon: Option<RconClient>,
+    /// Where backups are copied offsite, if anywhere
+    pub s3: Option<S3Client>,
+    pub shard_manager: Arc<ShardManager>,
+    /// Every line the server console prints, for commands waiting on their output
+    pub console_lines: broadcast::Sender<String>,
+    /// Held while a backup is made, restored or checked
+    pub backup_lock: Mutex<()>,
     pub mutables: RwLock<ServerStateMutables>,
 }
 
@@ -48,16 +72,116 @@ pub struct BotConfig {
     pub container_name: String,
     pub db_addr: String,
     pub guild_id: Option<u64>,
+    pub chat_bridge_channel_id: Option<u64>,
+    pub alert_channel_id: Option<u64>,
+    pub rcon_address: Option<String>,
+    pub rcon_password: Option<String>,
+    /// `host:port` the server accepts players on
+    pub server_address: String,
+    /// Lowercase names `/console` may run, `None` allows everything not denied
+    pub console_allowed_commands: Option<Vec<String>>,
+    pub console_denied_commands: Vec<String>,
+    /// Needs the privileged `GUILD_MEMBERS` intent
+    pub whitelist_remove_on_leave: bool,
+    /// Backups are turned off without a directory to put them in
+    pub backup_dir: Option<PathBuf>,
+    /// World directories inside the container
+    pub backup_paths: Vec<String>,
+    pub backup_interval: Option<Duration>,
+    pub backup_keep_daily: usize,
+    pub backup_keep_weekly: usize,
+    pub s3_endpoint: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_region: String,
+    pub s3_access_key_id: Option<String>,
+    pub s3_secret_access_key: Option<String>,
+    /// Put in front of every key, like `minecraft/`
+    pub s3_prefix: String,
 }
 
 impl BotConfig {
     pub fn initialise() -> Self {
+        let container_name = env_expect!("CONTAINER_NAME");
         Self {
-            container_name: env_expect!("CONTAINER_NAME"),
+            server_address: std::env::var("SERVER_ADDRESS")
+                .unwrap_or_else(|_| format!("{container_name}:25565")),
+            container_name,
             guild_id: std::env::var("GUILD_ID")
                 .ok()
                 .map(|id| id.parse().expect("GUILD_ID was not a positive number")),
             db_addr: env_expect!("DATABASE_URL"),
+            chat_bridge_channel_id: std::env::var("CHAT_BRIDGE_CHANNEL_ID").ok().map(|id| {
+                id.parse()
+                    .expect("CHAT_BRIDGE_CHANNEL_ID was not a positive number")
+            }),
+            alert_channel_id: std::env::var("ALERT_CHANNEL_ID").ok().map(|id| {
+                id.parse()
+                    .expect("ALERT_CHANNEL_ID was not a positive number")
+            }),
+            rcon_address: std::env::var("RCON_ADDRESS").ok(),
+            rcon_password: std::env::var("RCON_PASSWORD").ok(),
+            console_allowed_commands: std::env::var("CONSOLE_ALLOWED_COMMANDS")
+                .ok()
+                .map(|commands| command_list(&commands)),
+            // Stopping through the console would look like a crash, there is /stop for that
+            console_denied_commands: command_list(
+                &std::env::var("CONSOLE_DENIED_COMMANDS").unwrap_or_else(|_| "stop".to_string()),
+            ),
+            whitelist_remove_on_leave: std::env::var("WHITELIST_REMOVE_ON_LEAVE")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("WHITELIST_REMOVE_ON_LEAVE was not true or false")
+                })
+                .unwrap_or(false),
+            backup_dir: std::env::var("BACKUP_DIR").ok().map(PathBuf::from),
+            backup_paths: std::env::var("BACKUP_PATHS")
+                .unwrap_or_else(|_| {
+                    "/data/world,/data/world_nether,/data/world_the_end".to_string()
+                })
+                .split(',')
+                .map(|path| path.trim().to_string())
+                .filter(|path| !path.is_empty())
+                .collect(),
+            backup_interval: std::env::var("BACKUP_INTERVAL_HOURS").ok().map(|hours| {
+                Duration::from_secs(
+                    hours
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|hours| *hours > 0)
+                        .expect("BACKUP_INTERVAL_HOURS was not a positive number")
+                        * 60
+                        * 60,
+                )
+            }),
+            backup_keep_daily: std::env::var("BACKUP_KEEP_DAILY")
+                .ok()
+                .map(|n| {
+                    n.parse()
+                        .expect("BACKUP_KEEP_DAILY was not a positive number")
+                })
+                .unwrap_or(7),
+            backup_keep_weekly: std::env::var("BACKUP_KEEP_WEEKLY")
+                .ok()
+                .map(|n| {
+                    n.parse()
+                        .expect("BACKUP_KEEP_WEEKLY was not a positive number")
+                })
+                .unwrap_or(4),
+            s3_endpoint: std::env::var("S3_ENDPOINT").ok(),
+            s3_bucket: std::env::var("S3_BUCKET").ok(),
+            s3_region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            s3_access_key_id: std::env::var("S3_ACCESS_KEY_ID").ok(),
+            s3_secret_access_key: std::env::var("S3_SECRET_ACCESS_KEY").ok(),
+            s3_prefix: std::env::var("S3_PREFIX").unwrap_or_default(),
         }
     }
 }
+
+fn command_list(commands: &str) -> Vec<String> {
+    commands
+        .split(',')
+        .map(|command| command.trim().to_lowercase())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serenity::all::{GuildId, UserId};
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+#[derive(sqlx::FromRow)]
+pub struct AuditEntry {
+    pub created_at: DateTime<Utc>,
+    pub guild_id: Option<SqlU64>,
+    pub user_id: SqlU64,
+    pub user_name: String,
+    pub command: String,
+    pub options: String,
+    pub result: String,
+}
+
+impl AuditEntry {
+    pub fn insert(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO audit_log (created_at, guild_id, user_id, user_name, command, options, result)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            self.created_at,
+            self.guild_id.as_ref().map(SqlU64::to_db),
+            self.user_id.to_db(),
+            self.user_name,
+            self.command,
+            self.options,
+            self.result
+        )
+    }
+
+    /// Newest entries first, optionally only those of one user.
+    pub async fn get_recent(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: Option<UserId>,
+        limit: i64,
+    ) -> sqlx::Result<Vec<AuditEntry>> {
+        sqlx::query_as!(
+            AuditEntry,
+            r#"SELECT created_at, guild_id AS "guild_id: SqlU64", user_id AS "user_id: SqlU64",
+                user_name, command, options, result
+                FROM audit_log
+                WHERE guild_id = $1 AND ($2::BIGINT IS NULL OR user_id = $2)
+                ORDER BY created_at DESC
+                LIMIT $3"#,
+            SqlU64::from(guild_id.get()).to_db(),
+            user_id.map(|user_id| SqlU64::from(user_id.get()).to_db()),
+            limit
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct BackupRecord {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub size_bytes: i64,
+    /// The part of the size that was not already in the chunk store
+    pub stored_bytes: i64,
+    pub trigger: String,
+    pub created_by: Option<SqlU64>,
+}
+
+impl BackupRecord {
+    pub async fn insert(&self, pool: &PgPool) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            "INSERT INTO backup (created_at, name, size_bytes, stored_bytes, trigger, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id",
+            self.created_at,
+            self.name,
+            self.size_bytes,
+            self.stored_bytes,
+            self.trigger,
+            self.created_by.as_ref().map(SqlU64::to_db)
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    pub fn remove(
+        id: i64,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!("DELETE FROM backup WHERE id = $1", id)
+    }
+
+    /// Newest first.
+    pub async fn get_all(pool: &PgPool) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, created_at, name, size_bytes, stored_bytes, trigger,
+                created_by AS "created_by: SqlU64"
+                FROM backup ORDER BY created_at DESC"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn get(pool: &PgPool, id: i64) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, created_at, name, size_bytes, stored_bytes, trigger,
+                created_by AS "created_by: SqlU64"
+                FROM backup WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
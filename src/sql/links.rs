@@ -0,0 +1,70 @@
+use chrono::Utc;
+use serenity::all::UserId;
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+/// A Discord user and the Minecraft account they proved they own.
+#[derive(sqlx::FromRow)]
+pub struct AccountLink {
+    #[sqlx(try_from = "i64")]
+    pub discord_id: SqlU64,
+    pub player_name: String,
+}
+
+impl AccountLink {
+    pub fn insert<'a>(
+        discord_id: UserId,
+        player_name: &'a str,
+    ) -> sqlx::query::Query<'a, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO account_link (discord_id, player_name, linked_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (discord_id)
+                DO UPDATE SET player_name = $2, linked_at = $3",
+            SqlU64::from(discord_id.get()).to_db(),
+            player_name,
+            Utc::now()
+        )
+    }
+
+    pub fn remove(
+        discord_id: UserId,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM account_link WHERE discord_id = $1",
+            SqlU64::from(discord_id.get()).to_db()
+        )
+    }
+
+    /// Frees up the player name, for when someone else has proved they own it.
+    pub fn remove_player(
+        player_name: &str,
+    ) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM account_link WHERE lower(player_name) = lower($1)",
+            player_name
+        )
+    }
+
+    pub async fn get_for_user(pool: &PgPool, discord_id: UserId) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT discord_id, player_name FROM account_link WHERE discord_id = $1",
+            SqlU64::from(discord_id.get()).to_db()
+        )
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn get_for_player(pool: &PgPool, player_name: &str) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT discord_id, player_name FROM account_link
+                WHERE lower(player_name) = lower($1)",
+            player_name
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
@@ -1,23 +1,24 @@
 use sqlx::{Database, Decode, Encode, Type};
 
+pub mod audit;
+pub mod backups;
+pub mod links;
+pub mod permissions;
 pub mod player_join;
+pub mod schedules;
+pub mod sessions;
+pub mod stats;
+pub mod subscriptions;
+pub mod whitelist;
 
 #[derive(Debug)]
 pub struct SqlU64(u64);
 
 impl SqlU64 {
-    pub fn new(num: u64) -> Self {
-        Self(num)
-    }
-
     pub fn get(&self) -> u64 {
         self.0
     }
 
-    pub fn from_db(num: i64) -> Self {
-        Self(num as u64)
-    }
-    
     pub fn to_db(&self) -> i64 {
         self.0 as i64
     }
@@ -0,0 +1,137 @@
+use serenity::all::{GuildId, RoleId};
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+/// Groups of commands a role can be allowed to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Restart,
+    StartStop,
+    Logs,
+    Console,
+    Whitelist,
+    Backup,
+}
+
+impl Capability {
+    pub const CHOICES: [(&str, Capability); 6] = [
+        ("restart", Capability::Restart),
+        ("start_stop", Capability::StartStop),
+        ("logs", Capability::Logs),
+        ("console", Capability::Console),
+        ("whitelist", Capability::Whitelist),
+        ("backup", Capability::Backup),
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::CHOICES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, capability)| *capability)
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::CHOICES
+            .iter()
+            .find(|(_, capability)| capability == self)
+            .map(|(name, _)| *name)
+            .expect("every capability is a choice")
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct RoleCapability {
+    #[sqlx(try_from = "i64")]
+    pub role_id: SqlU64,
+    pub capability: String,
+}
+
+pub struct RolePermission {
+    guild_id: SqlU64,
+    role_id: SqlU64,
+    capability: Capability,
+}
+
+impl RolePermission {
+    pub fn new(guild_id: GuildId, role_id: RoleId, capability: Capability) -> Self {
+        Self {
+            guild_id: guild_id.get().into(),
+            role_id: role_id.get().into(),
+            capability,
+        }
+    }
+
+    pub fn insert(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO role_permission (guild_id, role_id, capability)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (guild_id, role_id, capability) DO NOTHING",
+            self.guild_id.to_db(),
+            self.role_id.to_db(),
+            self.capability.name()
+        )
+    }
+
+    pub fn remove(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM role_permission
+                WHERE guild_id = $1 AND role_id = $2 AND capability = $3",
+            self.guild_id.to_db(),
+            self.role_id.to_db(),
+            self.capability.name()
+        )
+    }
+
+    pub async fn get_for_guild(
+        pool: &PgPool,
+        guild_id: GuildId,
+    ) -> sqlx::Result<Vec<RoleCapability>> {
+        sqlx::query_as!(
+            RoleCapability,
+            "SELECT role_id, capability FROM role_permission
+                WHERE guild_id = $1
+                ORDER BY role_id, capability",
+            SqlU64::from(guild_id.get()).to_db()
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn any_role_has(
+        pool: &PgPool,
+        guild_id: GuildId,
+        role_ids: &[RoleId],
+        capability: Capability,
+    ) -> sqlx::Result<bool> {
+        let role_ids: Vec<i64> = role_ids
+            .iter()
+            .map(|role_id| SqlU64::from(role_id.get()).to_db())
+            .collect();
+        let allowed = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM role_permission
+                WHERE guild_id = $1 AND role_id = ANY($2) AND capability = $3) AS "allowed!""#,
+            SqlU64::from(guild_id.get()).to_db(),
+            &role_ids,
+            capability.name()
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(allowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capability;
+
+    #[test]
+    fn capability_names_round_trip() {
+        for (name, capability) in Capability::CHOICES {
+            assert_eq!(capability.name(), name);
+            assert_eq!(Capability::parse(name), Some(capability));
+        }
+        assert_eq!(Capability::parse("admin"), None);
+    }
+}
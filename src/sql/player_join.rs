@@ -1,56 +1,8 @@
-use serenity::all::{ChannelId, GuildId, UserId};
+use serenity::all::UserId;
 use sqlx::PgPool;
 
 use super::SqlU64;
 
-#[derive(sqlx::FromRow)]
-pub struct PlayerJoinServerChannel {
-    #[sqlx(try_from = "i64")]
-    pub guild_id: SqlU64,
-    #[sqlx(try_from = "i64")]
-    pub channel_id: SqlU64,
-}
-
-impl PlayerJoinServerChannel {
-    pub fn new(guild_id: GuildId, channel_id: ChannelId) -> Self {
-        Self {
-            guild_id: guild_id.get().into(),
-            channel_id: channel_id.get().into(),
-        }
-    }
-
-    pub async fn get_all_channels(pool: &PgPool) -> sqlx::Result<Vec<PlayerJoinServerChannel>> {
-        sqlx::query_as!(
-            PlayerJoinServerChannel,
-            "SELECT * FROM player_joined_server_channel"
-        )
-        .fetch_all(pool)
-        .await
-    }
-
-    pub fn insert_channel(
-        &self,
-    ) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
-        sqlx::query!(
-            "INSERT INTO player_joined_server_channel (guild_id, channel_id) 
-                VALUES ($1, $2)
-                ON CONFLICT (guild_id, channel_id) DO NOTHING",
-            self.guild_id.to_db(),
-            self.channel_id.to_db()
-        )
-    }
-
-    pub fn remove_channel(
-        &self,
-    ) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
-        sqlx::query!(
-            "DELETE FROM player_joined_server_channel WHERE guild_id = $1 AND channel_id = $2",
-            self.guild_id.to_db(),
-            self.channel_id.to_db()
-        )
-    }
-}
-
 #[derive(sqlx::FromRow)]
 pub struct PlayerJoinIgnore {
     discord_id: SqlU64,
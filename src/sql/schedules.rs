@@ -0,0 +1,90 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use serenity::all::GuildId;
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ScheduleRow {
+    pub id: i64,
+    pub guild_id: SqlU64,
+    pub created_by: SqlU64,
+    pub created_at: DateTime<Utc>,
+    pub weekday: Option<i16>,
+    pub time: NaiveTime,
+    pub timezone: String,
+    pub action: String,
+    pub argument: Option<String>,
+    pub channel_id: Option<SqlU64>,
+    pub last_run_at: DateTime<Utc>,
+}
+
+impl ScheduleRow {
+    pub async fn insert(&self, pool: &PgPool) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            "INSERT INTO schedule (guild_id, created_by, created_at, weekday, time, timezone,
+                action, argument, channel_id, last_run_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                RETURNING id",
+            self.guild_id.to_db(),
+            self.created_by.to_db(),
+            self.created_at,
+            self.weekday,
+            self.time,
+            self.timezone,
+            self.action,
+            self.argument,
+            self.channel_id.as_ref().map(SqlU64::to_db),
+            self.last_run_at
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    pub fn remove(
+        guild_id: GuildId,
+        id: i64,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM schedule WHERE guild_id = $1 AND id = $2",
+            SqlU64::from(guild_id.get()).to_db(),
+            id
+        )
+    }
+
+    pub fn set_last_run(
+        id: i64,
+        last_run_at: DateTime<Utc>,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "UPDATE schedule SET last_run_at = $2 WHERE id = $1",
+            id,
+            last_run_at
+        )
+    }
+
+    pub async fn get_all(pool: &PgPool) -> sqlx::Result<Vec<ScheduleRow>> {
+        sqlx::query_as!(
+            ScheduleRow,
+            r#"SELECT id, guild_id AS "guild_id: SqlU64", created_by AS "created_by: SqlU64",
+                created_at, weekday, time, timezone, action, argument,
+                channel_id AS "channel_id: SqlU64", last_run_at
+                FROM schedule ORDER BY id"#
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn get_for_guild(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<ScheduleRow>> {
+        sqlx::query_as!(
+            ScheduleRow,
+            r#"SELECT id, guild_id AS "guild_id: SqlU64", created_by AS "created_by: SqlU64",
+                created_at, weekday, time, timezone, action, argument,
+                channel_id AS "channel_id: SqlU64", last_run_at
+                FROM schedule WHERE guild_id = $1 ORDER BY id"#,
+            SqlU64::from(guild_id.get()).to_db()
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+#[derive(sqlx::FromRow)]
+pub struct PlaySession {
+    pub player_name: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub end_reason: Option<String>,
+}
+
+impl PlaySession {
+    pub fn start(
+        player_name: &str,
+        started_at: DateTime<Utc>,
+    ) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO play_session (player_name, started_at) VALUES ($1, $2)",
+            player_name,
+            started_at
+        )
+    }
+
+    pub fn end<'a>(
+        player_name: &'a str,
+        ended_at: DateTime<Utc>,
+        end_reason: &'a str,
+    ) -> sqlx::query::Query<'a, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "UPDATE play_session SET ended_at = $2, end_reason = $3
+                WHERE LOWER(player_name) = LOWER($1) AND ended_at IS NULL",
+            player_name,
+            ended_at,
+            end_reason
+        )
+    }
+
+    pub fn end_all(
+        ended_at: DateTime<Utc>,
+        end_reason: &str,
+    ) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "UPDATE play_session SET ended_at = $1, end_reason = $2 WHERE ended_at IS NULL",
+            ended_at,
+            end_reason
+        )
+    }
+
+    /// Closes sessions left open while the bot was not running.
+    ///
+    /// Sessions that started before the container did cannot have outlived it, so they end when
+    /// the container started. Anything newer may still be going, but we have lost track of it.
+    pub fn end_stale(
+        container_started_at: Option<DateTime<Utc>>,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "UPDATE play_session SET
+                ended_at = CASE WHEN $1::timestamptz > started_at THEN $1 ELSE now() END,
+                end_reason = CASE WHEN $1::timestamptz > started_at
+                    THEN 'server restarted' ELSE 'bot restarted' END
+                WHERE ended_at IS NULL",
+            container_started_at
+        )
+    }
+
+    /// Sessions of a player that overlap the period from `from` until now.
+    pub async fn get_since(
+        pool: &PgPool,
+        player_name: &str,
+        from: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<PlaySession>> {
+        sqlx::query_as!(
+            PlaySession,
+            "SELECT player_name, started_at, ended_at, end_reason FROM play_session
+                WHERE LOWER(player_name) = LOWER($1)
+                AND (ended_at IS NULL OR ended_at > $2)
+                ORDER BY started_at",
+            player_name,
+            from
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
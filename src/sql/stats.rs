@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::docker::ContainerStats;
+
+/// One sample of the container's resource usage.
+#[derive(sqlx::FromRow)]
+pub struct StatsSample {
+    pub recorded_at: DateTime<Utc>,
+    pub cpu_percent: f64,
+    pub memory_usage: i64,
+    pub memory_limit: i64,
+    pub network_rx: i64,
+    pub network_tx: i64,
+    pub block_read: i64,
+    pub block_write: i64,
+}
+
+/// Min, average and max of the samples over a period.
+#[derive(Debug, sqlx::FromRow)]
+pub struct StatsSummary {
+    pub samples: i64,
+    pub cpu_min: Option<f64>,
+    pub cpu_avg: Option<f64>,
+    pub cpu_max: Option<f64>,
+    pub memory_min: Option<i64>,
+    pub memory_avg: Option<f64>,
+    pub memory_max: Option<i64>,
+}
+
+impl StatsSample {
+    pub fn new(stats: &ContainerStats, recorded_at: DateTime<Utc>) -> Self {
+        Self {
+            recorded_at,
+            cpu_percent: stats.cpu_percent,
+            memory_usage: stats.memory_usage as i64,
+            memory_limit: stats.memory_limit as i64,
+            network_rx: stats.network_rx as i64,
+            network_tx: stats.network_tx as i64,
+            block_read: stats.block_read as i64,
+            block_write: stats.block_write as i64,
+        }
+    }
+
+    pub fn insert(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO container_stats (recorded_at, cpu_percent, memory_usage, memory_limit,
+                network_rx, network_tx, block_read, block_write)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            self.recorded_at,
+            self.cpu_percent,
+            self.memory_usage,
+            self.memory_limit,
+            self.network_rx,
+            self.network_tx,
+            self.block_read,
+            self.block_write
+        )
+    }
+
+    pub fn delete_before(
+        before: DateTime<Utc>,
+    ) -> sqlx::query::Query<'static, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!("DELETE FROM container_stats WHERE recorded_at < $1", before)
+    }
+
+    pub async fn summary_since(pool: &PgPool, from: DateTime<Utc>) -> sqlx::Result<StatsSummary> {
+        sqlx::query_as!(
+            StatsSummary,
+            r#"SELECT COUNT(*) AS "samples!",
+                MIN(cpu_percent) AS cpu_min, AVG(cpu_percent) AS cpu_avg, MAX(cpu_percent) AS cpu_max,
+                MIN(memory_usage) AS memory_min, AVG(memory_usage)::DOUBLE PRECISION AS memory_avg,
+                MAX(memory_usage) AS memory_max
+                FROM container_stats WHERE recorded_at >= $1"#,
+            from
+        )
+        .fetch_one(pool)
+        .await
+    }
+}
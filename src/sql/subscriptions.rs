@@ -0,0 +1,112 @@
+use serenity::all::{ChannelId, GuildId};
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+/// Things that happen in game that a channel can be told about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    Join,
+    Leave,
+    Death,
+    Advancement,
+}
+
+impl EventType {
+    pub const CHOICES: [(&str, EventType); 4] = [
+        ("join", EventType::Join),
+        ("leave", EventType::Leave),
+        ("death", EventType::Death),
+        ("advancement", EventType::Advancement),
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::CHOICES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, event_type)| *event_type)
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::CHOICES
+            .iter()
+            .find(|(_, event_type)| event_type == self)
+            .map(|(name, _)| *name)
+            .expect("every event type is a choice")
+    }
+
+    /// Fits in "This channel will now announce ..."
+    pub fn describe(&self) -> &'static str {
+        match self {
+            EventType::Join => "when players join the minecraft server",
+            EventType::Leave => "when players leave the minecraft server",
+            EventType::Death => "player deaths",
+            EventType::Advancement => "advancements players make",
+        }
+    }
+}
+
+pub struct EventSubscription {
+    guild_id: SqlU64,
+    channel_id: SqlU64,
+    event_type: EventType,
+}
+
+impl EventSubscription {
+    pub fn new(guild_id: GuildId, channel_id: ChannelId, event_type: EventType) -> Self {
+        Self {
+            guild_id: guild_id.get().into(),
+            channel_id: channel_id.get().into(),
+            event_type,
+        }
+    }
+
+    pub fn event_type(&self) -> EventType {
+        self.event_type
+    }
+
+    pub async fn get_channels(pool: &PgPool, event_type: EventType) -> sqlx::Result<Vec<SqlU64>> {
+        sqlx::query_scalar!(
+            r#"SELECT channel_id AS "channel_id: SqlU64" FROM event_subscription
+                WHERE event_type = $1"#,
+            event_type.name()
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub fn insert(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO event_subscription (guild_id, channel_id, event_type)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (guild_id, channel_id, event_type) DO NOTHING",
+            self.guild_id.to_db(),
+            self.channel_id.to_db(),
+            self.event_type.name()
+        )
+    }
+
+    pub fn remove(&self) -> sqlx::query::Query<'_, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM event_subscription
+                WHERE guild_id = $1 AND channel_id = $2 AND event_type = $3",
+            self.guild_id.to_db(),
+            self.channel_id.to_db(),
+            self.event_type.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventType;
+
+    #[test]
+    fn event_type_names_round_trip() {
+        for (name, event_type) in EventType::CHOICES {
+            assert_eq!(event_type.name(), name);
+            assert_eq!(EventType::parse(name), Some(event_type));
+        }
+        assert_eq!(EventType::parse("chat"), None);
+    }
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serenity::all::{GuildId, UserId};
+use sqlx::PgPool;
+
+use super::SqlU64;
+
+#[derive(sqlx::FromRow)]
+pub struct WhitelistEntry {
+    pub player_name: String,
+    #[sqlx(try_from = "i64")]
+    pub user_id: SqlU64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WhitelistEntry {
+    pub fn insert<'a>(
+        guild_id: GuildId,
+        player_name: &'a str,
+        user_id: UserId,
+    ) -> sqlx::query::Query<'a, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "INSERT INTO whitelist (guild_id, player_name, user_id, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (guild_id, lower(player_name)) DO UPDATE SET user_id = $3",
+            SqlU64::from(guild_id.get()).to_db(),
+            player_name,
+            SqlU64::from(user_id.get()).to_db(),
+            Utc::now()
+        )
+    }
+
+    /// Minecraft names are not case sensitive.
+    pub fn remove<'a>(
+        guild_id: GuildId,
+        player_name: &'a str,
+    ) -> sqlx::query::Query<'a, sqlx::Postgres, sqlx::postgres::PgArguments> {
+        sqlx::query!(
+            "DELETE FROM whitelist WHERE guild_id = $1 AND lower(player_name) = lower($2)",
+            SqlU64::from(guild_id.get()).to_db(),
+            player_name
+        )
+    }
+
+    pub async fn get_for_guild(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT player_name, user_id, created_at FROM whitelist
+                WHERE guild_id = $1
+                ORDER BY lower(player_name)",
+            SqlU64::from(guild_id.get()).to_db()
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn get_names_for_user(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> sqlx::Result<Vec<String>> {
+        sqlx::query_scalar!(
+            "SELECT player_name FROM whitelist WHERE guild_id = $1 AND user_id = $2",
+            SqlU64::from(guild_id.get()).to_db(),
+            SqlU64::from(user_id.get()).to_db()
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,181 @@
+//! Keeps the bot attached to the server console across restarts, recreations and dropped streams.
+
+use std::{
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::{all::Http, futures::StreamExt};
+
+use crate::{
+    active_features::{self, alerts},
+    docker, lifecycle, log_parser,
+    server_state::ServerState,
+};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Failed attempts in a row before the admins are told
+const ALERT_AFTER_FAILURES: u32 = 5;
+/// An attachment that ends sooner than this counts as a failed attempt
+const HEALTHY_ATTACHMENT: Duration = Duration::from_secs(10);
+/// How often to look at the container while waiting for it to start, in case an event was missed
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self { failures: 0 }
+    }
+
+    /// Records a failed attempt and returns how long to wait before the next one.
+    fn fail(&mut self) -> Duration {
+        self.failures += 1;
+        MIN_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.failures - 1))
+            .min(MAX_BACKOFF)
+    }
+
+    /// Returns how many attempts had failed in a row.
+    fn reset(&mut self) -> u32 {
+        std::mem::replace(&mut self.failures, 0)
+    }
+}
+
+/// Attaches to the console and re-attaches whenever the stream ends, for as long as the bot runs.
+pub async fn supervise(server_state: Arc<ServerState>, http: Arc<Http>) {
+    let mut backoff = Backoff::new();
+    loop {
+        let result = match wait_until_running(&server_state).await {
+            Ok(()) => listen(&server_state, &http, &mut backoff).await,
+            Err(e) => Err(e),
+        };
+
+        let reason = match result {
+            Ok(attached_for) if attached_for >= HEALTHY_ATTACHMENT => {
+                log::info!("Console stream ended, re-attaching");
+                continue;
+            }
+            Ok(_) => "the stream closed straight away".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        let delay = backoff.fail();
+        log::warn!(
+            "Could not attach to the server console ({} attempts): {reason}. Retrying in {}s",
+            backoff.failures,
+            delay.as_secs()
+        );
+        if backoff.failures == ALERT_AFTER_FAILURES {
+            let _ = alerts::alert(
+                &server_state,
+                &http,
+                format!(
+                    ":warning: Lost the server console and could not re-attach after {} attempts: {reason}\n\
+                    Announcements and the chat bridge are paused until it is back.",
+                    backoff.failures
+                ),
+            )
+            .await
+            .map_err(|e| log::error!("Error in alert: {e}"));
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Forwards console lines to the features until the stream ends, returning how long it lasted.
+async fn listen(
+    server_state: &Arc<ServerState>,
+    http: &Arc<Http>,
+    backoff: &mut Backoff,
+) -> Result<Duration, bollard::errors::Error> {
+    let func = |s: String| {
+        let http = http.clone();
+        let server_state = server_state.clone();
+        async move {
+            // Nobody listening is fine
+            let _ = server_state.console_lines.send(s.clone());
+            let line = log_parser::parse(&s);
+            active_features::handle_event(&server_state, &http, &line.event).await;
+        }
+    };
+
+    let attached_at = Instant::now();
+    let mut attachment = pin!(docker::attach_and_listen(server_state, func));
+    let result = tokio::select! {
+        result = &mut attachment => result,
+        () = tokio::time::sleep(HEALTHY_ATTACHMENT) => {
+            if backoff.reset() >= ALERT_AFTER_FAILURES {
+                let _ = alerts::alert(
+                    server_state,
+                    http,
+                    ":white_check_mark: Re-attached to the server console",
+                )
+                .await
+                .map_err(|e| log::error!("Error in alert: {e}"));
+            }
+            attachment.await
+        }
+    };
+    lifecycle::detached(server_state).await;
+
+    result.map(|()| attached_at.elapsed())
+}
+
+/// Returns once the container is running, which after a `/stop` can take a while.
+async fn wait_until_running(server_state: &ServerState) -> Result<(), bollard::errors::Error> {
+    loop {
+        if docker::container_status(server_state).await?.running {
+            return Ok(());
+        }
+        log::debug!(
+            "Waiting for container {} to start",
+            server_state.bot_config.container_name
+        );
+
+        let subscribed_at = Instant::now();
+        let mut events = pin!(docker::container_events(server_state));
+        // Recreating the container shows up as a start as well
+        while let Ok(Some(event)) = tokio::time::timeout(RECHECK_INTERVAL, events.next()).await {
+            if event?.action.as_deref() == Some("start") {
+                return Ok(());
+            }
+        }
+        // A stream that ends straight away, like while docker restarts, would otherwise spin
+        tokio::time::sleep(RECHECK_INTERVAL.saturating_sub(subscribed_at.elapsed())).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<_> = (0..9).map(|_| backoff.fail().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60, 60]);
+
+        for _ in 0..100 {
+            backoff.fail();
+        }
+        assert_eq!(backoff.fail(), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn backoff_reset() {
+        let mut backoff = Backoff::new();
+        assert_eq!(backoff.reset(), 0);
+
+        backoff.fail();
+        backoff.fail();
+        backoff.fail();
+        assert_eq!(backoff.reset(), 3);
+        assert_eq!(backoff.fail(), MIN_BACKOFF);
+    }
+}